@@ -62,9 +62,11 @@ use substrate_api_client::{
 };
 
 use substrate_client_keystore::LocalKeystore;
-use substratee_stf::{ShardIdentifier, TrustedCallSigned, TrustedOperation};
+use substratee_stf::{ShardIdentifier, StfEvent, TrustedCallSigned, TrustedOperation};
 use substratee_worker_api::direct_client::DirectApi as DirectWorkerApi;
-use substratee_worker_primitives::{DirectRequestStatus, RpcRequest, RpcResponse, RpcReturnValue};
+use substratee_worker_primitives::{
+    request_hash, DirectRequestStatus, GetterResponseKey, RpcRequest, TrustedOperationStatus,
+};
 
 type AccountPublic = <Signature as Verify>::Signer;
 const KEYSTORE_PATH: &str = "my_keystore";
@@ -371,11 +373,21 @@ fn main() {
                             .value_name("STRING")
                             .help("Shard identifier"),
                     )
+                    .arg(
+                        Arg::with_name("asset")
+                            .long("asset")
+                            .takes_value(true)
+                            .value_name("U32")
+                            .default_value("0")
+                            .help("Asset to be shielded, 0 is the native token"),
+                    )
                 })
                 .runner(move |_args: &str, matches: &ArgMatches<'_>| {
                     let chain_api = get_chain_api(matches);
                     let amount = u128::from_str_radix(matches.value_of("amount").unwrap(), 10)
                         .expect("amount can't be converted to u128");
+                    let asset_id = u32::from_str_radix(matches.value_of("asset").unwrap(), 10)
+                        .expect("asset can't be converted to u32");
 
                     let shard_opt = match matches.value_of("shard") {
                         Some(s) => match s.from_base58() {
@@ -402,11 +414,12 @@ fn main() {
                         Err(e) => panic!(e),
                     };
                     // compose the extrinsic
-                    let xt: UncheckedExtrinsicV4<([u8; 2], Vec<u8>, u128, H256)> = compose_extrinsic!(
+                    let xt: UncheckedExtrinsicV4<([u8; 2], Vec<u8>, u32, u128, H256)> = compose_extrinsic!(
                         chain_api,
                         "SubstrateeRegistry",
                         "shield_funds",
                         to_encrypted,
+                        asset_id,
                         amount,
                         shard
                     );
@@ -451,14 +464,16 @@ fn perform_trusted_operation(matches: &ArgMatches<'_>, top: &TrustedOperation) -
 
 fn get_state(matches: &ArgMatches<'_>, getter: TrustedOperation) -> Option<Vec<u8>> {
     // TODO: ensure getter is signed?
-    let (_operation_call_encoded, operation_call_encrypted) = match encode_encrypt(matches, getter)
-    {
-        Ok((encoded, encrypted)) => (encoded, encrypted),
-        Err(msg) => {
-            println!("[Error] {}", msg);
-            return None;
-        }
-    };
+    // the enclave encrypts the result with a fresh key, which only travels shielded
+    let response_key = GetterResponseKey::new(rand::random(), rand::random());
+    let (_operation_call_encoded, operation_call_encrypted) =
+        match encode_encrypt(matches, (getter, response_key)) {
+            Ok((encoded, encrypted)) => (encoded, encrypted),
+            Err(msg) => {
+                println!("[Error] {}", msg);
+                return None;
+            }
+        };
     let shard = read_shard(matches).unwrap();
 
     // compose jsonrpc call
@@ -467,6 +482,7 @@ fn get_state(matches: &ArgMatches<'_>, getter: TrustedOperation) -> Option<Vec<u
         cyphertext: operation_call_encrypted,
     };
     let rpc_method = "author_submitAndWatchExtrinsic".to_owned();
+    let getter_request_hash = request_hash(&rpc_method, &data.encode());
     let jsonrpc_call: String = RpcRequest::compose_jsonrpc_call(rpc_method, data.encode());
 
     let direct_api = get_worker_api_direct(matches);
@@ -479,22 +495,29 @@ fn get_state(matches: &ArgMatches<'_>, getter: TrustedOperation) -> Option<Vec<u
     loop {
         match receiver.recv() {
             Ok(response) => {
-                let response: RpcResponse = serde_json::from_str(&response).unwrap();
-                if let Ok(return_value) = RpcReturnValue::decode(&mut response.result.as_slice()) {
-                    if return_value.status == DirectRequestStatus::Error {
-                        println!(
-                            "[Error] {}",
-                            String::decode(&mut return_value.value.as_slice()).unwrap()
-                        );
-                        return None;
-                    }
-                    if !return_value.do_watch {
-                        return match Option::decode(&mut return_value.value.as_slice()) {
-                            Ok(value_opt) => value_opt,
-                            Err(_) => panic!("Error when decoding response"),
-                        };
-                    }
-                };
+                let return_value =
+                    match direct_api.decode_return_value(&response, &getter_request_hash, &shard) {
+                        Ok(return_value) => return_value,
+                        Err(msg) => {
+                            println!("[Error] {}", msg);
+                            return None;
+                        }
+                    };
+                if return_value.status == DirectRequestStatus::Error {
+                    println!(
+                        "[Error] {}",
+                        String::decode(&mut return_value.value.as_slice()).unwrap()
+                    );
+                    return None;
+                }
+                if !return_value.do_watch {
+                    let mut value = return_value.value;
+                    response_key.de_or_encrypt(&mut value);
+                    return match Option::decode(&mut value.as_slice()) {
+                        Ok(value_opt) => value_opt,
+                        Err(_) => panic!("Error when decoding response"),
+                    };
+                }
             }
             Err(_) => return None,
         };
@@ -582,7 +605,22 @@ fn get_worker_api_direct(matches: &ArgMatches<'_>) -> DirectWorkerApi {
         matches.value_of("worker-rpc-port").unwrap()
     );
     info!("Connecting to substraTEE-worker-direct-port on '{}'", url);
-    DirectWorkerApi::new(url)
+    let direct_api = DirectWorkerApi::new(url);
+    if matches.value_of("shard").is_none() && matches.value_of("mrenclave").is_none() {
+        return direct_api;
+    }
+    // enclave responses are verified against the signing key registered on chain
+    let shard = read_shard(matches).unwrap();
+    match get_worker_for_shard(&get_chain_api(matches), &shard) {
+        Some(enclave) => direct_api.with_enclave_signer(enclave.pubkey),
+        None => {
+            warn!(
+                "No worker registered for shard {}, enclave responses are not verified",
+                shard.encode().to_base58()
+            );
+            direct_api
+        }
+    }
 }
 
 fn read_shard(matches: &ArgMatches<'_>) -> StdResult<ShardIdentifier, codec::Error> {
@@ -626,6 +664,8 @@ fn send_direct_request(
         params: data.encode(),
         id: 1,
     };
+    // the status updates following the submission answer the operation it returns
+    let mut answered_hash = request_hash(&direct_invocation_call.method, &data.encode());
     let jsonrpc_call: String = serde_json::to_string(&direct_invocation_call).unwrap();
 
     let direct_api = get_worker_api_direct(matches);
@@ -638,26 +678,43 @@ fn send_direct_request(
     loop {
         match receiver.recv() {
             Ok(response) => {
-                let response: RpcResponse = serde_json::from_str(&response).unwrap();
-                if let Ok(return_value) = RpcReturnValue::decode(&mut response.result.as_slice()) {
-                    match return_value.status {
-                        DirectRequestStatus::Error => {
-                            if let Ok(value) = String::decode(&mut return_value.value.as_slice()) {
-                                println!("[Error] {}", value);
-                            }
+                let return_value =
+                    match direct_api.decode_return_value(&response, &answered_hash, &shard) {
+                        Ok(return_value) => return_value,
+                        Err(msg) => {
+                            println!("[Error] {}", msg);
                             return None;
                         }
-                        DirectRequestStatus::TrustedOperationStatus(status) => {
-                            if let Ok(value) = Hash::decode(&mut return_value.value.as_slice()) {
-                                println!("Trusted call {:?} is {:?}", value, status);
-                            }
+                    };
+                match return_value.status {
+                    DirectRequestStatus::Error => {
+                        if let Ok(value) = String::decode(&mut return_value.value.as_slice()) {
+                            println!("[Error] {}", value);
                         }
-                        _ => return None,
-                    }
-                    if !return_value.do_watch {
                         return None;
                     }
-                };
+                    DirectRequestStatus::TrustedOperationStatus(
+                        TrustedOperationStatus::Executed,
+                    ) => {
+                        if let Ok(events) =
+                            Vec::<StfEvent>::decode(&mut return_value.value.as_slice())
+                        {
+                            println!("Trusted call is executed, events: {:?}", events);
+                        }
+                    }
+                    DirectRequestStatus::TrustedOperationStatus(status) => {
+                        if let Ok(value) = Hash::decode(&mut return_value.value.as_slice()) {
+                            println!("Trusted call {:?} is {:?}", value, status);
+                            if status == TrustedOperationStatus::Submitted {
+                                answered_hash = value;
+                            }
+                        }
+                    }
+                    _ => return None,
+                }
+                if !return_value.do_watch {
+                    return None;
+                }
             }
             Err(_) => return None,
         };
@@ -882,3 +939,12 @@ fn get_enclave(api: &Api<sr25519::Pair>, eindex: u64) -> Option<Enclave<AccountI
     api.get_storage_map("SubstrateeRegistry", "EnclaveRegistry", eindex, None)
         .unwrap()
 }
+
+fn get_worker_for_shard(
+    api: &Api<sr25519::Pair>,
+    shard: &ShardIdentifier,
+) -> Option<Enclave<AccountId, Vec<u8>>> {
+    api.get_storage_map("SubstrateeRegistry", "WorkerForShard", shard, None)
+        .unwrap()
+        .and_then(|eindex| get_enclave(api, eindex))
+}
@@ -21,6 +21,8 @@ pub enum Error {
     StorageValueUnavailable,
     // InvalidValidatorSetProof,
     ValidatorSetMismatch,
+    ValidatorSetIdMismatch,
+    GenesisHashMismatch,
     InvalidAncestryProof,
     NoSuchRelayExists,
     InvalidFinalityProof,
@@ -23,6 +23,7 @@ extern crate sgx_tstd as std;
 
 pub mod error;
 pub mod justification;
+pub mod migration;
 pub mod state;
 pub mod storage_proof;
 
@@ -36,7 +37,7 @@ use justification::GrandpaJustification;
 use state::RelayState;
 use storage_proof::StorageProof;
 
-use crate::state::ScheduledChangeAtBlock;
+use crate::state::{Checkpoint, PendingExtrinsic, ScheduledChangeAtBlock, StoredPendingChange};
 use crate::storage_proof::StorageProofChecker;
 use codec::{Decode, Encode};
 use finality_grandpa::voter_set::VoterSet;
@@ -53,7 +54,7 @@ use sp_runtime::traits::{
 };
 use sp_runtime::{Justifications, Justification, OpaqueExtrinsic};
 
-type RelayId = u64;
+pub type RelayId = u64;
 pub type Blocknumber = u32;
 pub type Header = HeaderG<Blocknumber, BlakeTwo256>;
 pub type Block = BlockG<Header, OpaqueExtrinsic>;
@@ -72,20 +73,50 @@ impl LightValidation {
         LightValidation::default()
     }
 
+    /// Starts tracking a chain from a trusted finalized header, e.g. its genesis header.
+    ///
+    /// The validator set and its id are checked against the proof of the grandpa storage
+    /// at the header. The proof must contain the current set id and the pending change.
+    /// A genesis header must have the hash of the chain's genesis.
     pub fn initialize_relay(
         &mut self,
+        genesis_hash: <Header as HeaderT>::Hash,
         block_header: Header,
         validator_set: AuthorityList,
+        validator_set_id: SetId,
         validator_set_proof: StorageProof,
     ) -> Result<RelayId, Error> {
+        if *block_header.number() == 0 && block_header.hash() != genesis_hash {
+            return Err(Error::GenesisHashMismatch);
+        }
         let state_root = block_header.state_root();
-        Self::check_validator_set_proof::<<Header as HeaderT>::Hashing>(
-            state_root,
+        let checker = StorageProofChecker::<<Header as HeaderT>::Hashing>::new(
+            *state_root,
             validator_set_proof,
-            &validator_set,
         )?;
+        Self::check_validator_set_proof(&checker, &validator_set)?;
+        if Self::read_validator_set_id(&checker)? != validator_set_id {
+            return Err(Error::ValidatorSetIdMismatch);
+        }
 
-        let relay_info = RelayState::new(block_header, validator_set);
+        // the set id is increased as soon as a change is scheduled,
+        // but the validator set only changes when it is enacted
+        let pending_change = Self::read_pending_change(&checker)?;
+        let current_validator_set_id = match pending_change {
+            Some(_) => validator_set_id.saturating_sub(1),
+            None => validator_set_id,
+        };
+
+        let mut relay_info = RelayState::new(
+            genesis_hash,
+            block_header,
+            validator_set,
+            current_validator_set_id,
+        );
+        relay_info.scheduled_change = pending_change.map(|change| ScheduledChangeAtBlock {
+            at_block: change.scheduled_at + change.delay,
+            next_authority_list: change.next_authorities,
+        });
 
         let new_relay_id = self.num_relays + 1;
         self.tracked_relays.insert(new_relay_id, relay_info);
@@ -115,6 +146,8 @@ impl LightValidation {
 
         if grandpa_proofs.is_none() {
             relay.last_finalized_block_header = header.clone();
+            // forced changes are signaled while finality is stalled, i.e. in headers without proof
+            Self::schedule_validator_set_change(&mut relay, &header);
             relay.unjustified_headers.push(header.hash());
             debug!(
                 "Syncing finalized block without grandpa proof. Amount of unjustified headers: {}",
@@ -153,6 +186,17 @@ impl LightValidation {
         relay.header_hashes.append(&mut relay.unjustified_headers);
         relay.header_hashes.push(header.hash());
 
+        // the first justified header of a validator set is kept as checkpoint
+        if relay.checkpoints.last().map_or(true, |checkpoint| {
+            checkpoint.validator_set_id < validator_set_id
+        }) {
+            relay.checkpoints.push(Checkpoint {
+                number: block_num,
+                hash: block_hash,
+                validator_set_id,
+            });
+        }
+
         if validator_set_id > relay.current_validator_set_id {
             relay.current_validator_set = validator_set;
             relay.current_validator_set_id = validator_set_id;
@@ -191,16 +235,23 @@ impl LightValidation {
         )
     }
 
+    /// Awaits the inclusion of an extrinsic sent to the chain, `call` being its encoded call
     pub fn submit_xt_to_be_included(
         &mut self,
         relay_id: RelayId,
         extrinsic: OpaqueExtrinsic,
+        call: Vec<u8>,
     ) -> Result<(), Error> {
         let relay = self
             .tracked_relays
             .get_mut(&relay_id)
             .ok_or(Error::NoSuchRelayExists)?;
-        relay.verify_tx_inclusion.push(extrinsic);
+        let submitted_at = *relay.last_finalized_block_header.number();
+        relay.verify_tx_inclusion.push(PendingExtrinsic {
+            extrinsic,
+            call,
+            submitted_at,
+        });
         Ok(())
     }
 
@@ -216,9 +267,9 @@ impl LightValidation {
 
         let mut found_xts = vec![];
         block.extrinsics.iter().for_each(|xt| {
-            if let Some(index) = relay.verify_tx_inclusion.iter().position(|xt_opaque| {
+            if let Some(index) = relay.verify_tx_inclusion.iter().position(|pending| {
                 <<Header as HeaderT>::Hashing>::hash_of(xt)
-                    == <<Header as HeaderT>::Hashing>::hash_of(xt_opaque)
+                    == <<Header as HeaderT>::Hashing>::hash_of(&pending.extrinsic)
             }) {
                 found_xts.push(index);
             }
@@ -227,7 +278,7 @@ impl LightValidation {
         // sort highest index first
         found_xts.sort_by(|a, b| b.cmp(a));
 
-        let rm: Vec<OpaqueExtrinsic> = found_xts
+        let rm: Vec<PendingExtrinsic<Header>> = found_xts
             .into_iter()
             .map(|i| relay.verify_tx_inclusion.remove(i))
             .collect();
@@ -247,12 +298,84 @@ impl LightValidation {
         Ok(relay.verify_tx_inclusion.len())
     }
 
+    /// Removes and returns the extrinsics which have not been included within `timeout`
+    /// finalized blocks after they were sent, so that they can be sent again.
+    pub fn take_stuck_extrinsics(
+        &mut self,
+        relay_id: RelayId,
+        timeout: Blocknumber,
+    ) -> Result<Vec<PendingExtrinsic<Header>>, Error> {
+        let relay = self
+            .tracked_relays
+            .get_mut(&relay_id)
+            .ok_or(Error::NoSuchRelayExists)?;
+        let finalized = *relay.last_finalized_block_header.number();
+
+        let (stuck, pending): (Vec<_>, Vec<_>) = relay
+            .verify_tx_inclusion
+            .drain(..)
+            .partition(|xt| finalized.saturating_sub(xt.submitted_at) >= timeout);
+        relay.verify_tx_inclusion = pending;
+
+        if !stuck.is_empty() {
+            warn!(
+                "{} extrinsics have not been included within {} blocks on relay {}",
+                stuck.len(),
+                timeout,
+                relay_id
+            );
+            relay.num_stuck_extrinsics += stuck.len() as u64;
+        }
+        Ok(stuck)
+    }
+
+    /// Returns the number of extrinsics reported as stuck since the relay was initialized
+    pub fn num_stuck_extrinsics(&self, relay_id: RelayId) -> Result<u64, Error> {
+        let relay = self
+            .tracked_relays
+            .get(&relay_id)
+            .ok_or(Error::NoSuchRelayExists)?;
+        Ok(relay.num_stuck_extrinsics)
+    }
+
+    /// Returns the id of the relay tracking the chain with the given genesis hash, if any
+    pub fn relay_id(&self, genesis_hash: &<Header as HeaderT>::Hash) -> Option<RelayId> {
+        self.tracked_relays
+            .iter()
+            .find(|(_, relay)| relay.genesis_hash == *genesis_hash)
+            .map(|(relay_id, _)| *relay_id)
+    }
+
     pub fn genesis_hash(&self, relay_id: RelayId) -> Result<<Header as HeaderT>::Hash, Error> {
         let relay = self
             .tracked_relays
             .get(&relay_id)
             .ok_or(Error::NoSuchRelayExists)?;
-        Ok(relay.header_hashes[0])
+        Ok(relay.genesis_hash)
+    }
+
+    /// Keeps only the latest `retention` header hashes, unjustified headers and checkpoints
+    /// of the relay. The genesis hash and the last finalized header are kept regardless.
+    pub fn prune_relay(&mut self, relay_id: RelayId, retention: usize) -> Result<(), Error> {
+        let relay = self
+            .tracked_relays
+            .get_mut(&relay_id)
+            .ok_or(Error::NoSuchRelayExists)?;
+        let num_pruned = retain_latest(&mut relay.header_hashes, retention)
+            + retain_latest(&mut relay.unjustified_headers, retention)
+            + retain_latest(&mut relay.checkpoints, retention);
+        if num_pruned > 0 {
+            debug!("Pruned {} entries of relay {}", num_pruned, relay_id);
+        }
+        Ok(())
+    }
+
+    pub fn checkpoints(&self, relay_id: RelayId) -> Result<Vec<Checkpoint<Header>>, Error> {
+        let relay = self
+            .tracked_relays
+            .get(&relay_id)
+            .ok_or(Error::NoSuchRelayExists)?;
+        Ok(relay.checkpoints.clone())
     }
 
     pub fn latest_finalized_header(&self, relay_id: RelayId) -> Result<Header, Error> {
@@ -268,9 +391,12 @@ impl LightValidation {
         header: &Block::Header,
     ) {
         if let Some(change) = relay.scheduled_change.take() {
-            if &change.at_block == header.number() {
+            // the block enacting the change is the last one finalized by the old validator set
+            if header.number() > &change.at_block {
                 relay.current_validator_set = change.next_authority_list;
                 relay.current_validator_set_id += 1;
+            } else {
+                relay.scheduled_change = Some(change);
             }
         }
     }
@@ -279,7 +405,17 @@ impl LightValidation {
         relay: &mut RelayState<Block>,
         header: &Block::Header,
     ) {
-        if let Some(log) = pending_change::<Block::Header>(&header.digest()) {
+        // A forced change is enacted after its delay regardless of finality and replaces
+        // a scheduled change. The median last finalized block it carries only matters to voters.
+        if let Some((_, log)) = forced_change::<Block::Header>(&header.digest()) {
+            if relay.scheduled_change.is_some() {
+                warn!("Forced authorities change replaces the scheduled one");
+            }
+            relay.scheduled_change = Some(ScheduledChangeAtBlock {
+                at_block: log.delay + *header.number(),
+                next_authority_list: log.next_authorities,
+            });
+        } else if let Some(log) = pending_change::<Block::Header>(&header.digest()) {
             if relay.scheduled_change.is_some() {
                 error!(
                     "Tried to scheduled authorities change even though one is already scheduled!!"
@@ -294,12 +430,9 @@ impl LightValidation {
     }
 
     fn check_validator_set_proof<Hash: HashT>(
-        state_root: &Hash::Out,
-        proof: StorageProof,
+        checker: &StorageProofChecker<Hash>,
         validator_set: AuthorityListRef,
     ) -> Result<(), Error> {
-        let checker = StorageProofChecker::<Hash>::new(*state_root, proof)?;
-
         // By encoding the given set we should have an easy way to compare
         // with the stuff we get out of storage via `read_value`
         let mut encoded_validator_set = validator_set.encode();
@@ -315,6 +448,28 @@ impl LightValidation {
         }
     }
 
+    fn read_validator_set_id<Hash: HashT>(
+        checker: &StorageProofChecker<Hash>,
+    ) -> Result<SetId, Error> {
+        // the set id is not stored before the first change
+        match checker.read_value(&grandpa_storage_key("CurrentSetId"))? {
+            Some(set_id) => {
+                SetId::decode(&mut set_id.as_slice()).map_err(|_| Error::StorageValueUnavailable)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn read_pending_change<Hash: HashT>(
+        checker: &StorageProofChecker<Hash>,
+    ) -> Result<Option<StoredPendingChange<Blocknumber>>, Error> {
+        checker
+            .read_value(&grandpa_storage_key("PendingChange"))?
+            .map(|change| StoredPendingChange::decode(&mut change.as_slice()))
+            .transpose()
+            .map_err(|_| Error::StorageValueUnavailable)
+    }
+
     fn verify_grandpa_proof<Block>(
         justification: Justification,
         hash: Block::Hash,
@@ -372,13 +527,48 @@ impl LightValidation {
     }
 }
 
+/// Key of a storage value of the grandpa pallet, whose storage prefix is `GrandpaFinality`
+pub fn grandpa_storage_key(storage_name: &str) -> Vec<u8> {
+    let mut key = sp_core::twox_128(b"GrandpaFinality").to_vec();
+    key.extend(&sp_core::twox_128(storage_name.as_bytes())[..]);
+    key
+}
+
 pub fn grandpa_log<H: HeaderT>(digest: &DigestG<H::Hash>) -> Option<ConsensusLog<H::Number>> {
     let id = OpaqueDigestItemId::Consensus(&GRANDPA_ENGINE_ID);
     digest.convert_first(|l| l.try_to::<ConsensusLog<H::Number>>(id))
 }
 
+/// All grandpa logs of the digest, in order
+pub fn grandpa_logs<H: HeaderT>(digest: &DigestG<H::Hash>) -> Vec<ConsensusLog<H::Number>> {
+    let id = OpaqueDigestItemId::Consensus(&GRANDPA_ENGINE_ID);
+    digest
+        .logs()
+        .iter()
+        .filter_map(|l| l.try_to::<ConsensusLog<H::Number>>(id))
+        .collect()
+}
+
 pub fn pending_change<H: HeaderT>(digest: &DigestG<H::Hash>) -> Option<ScheduledChange<H::Number>> {
-    grandpa_log::<H>(digest).and_then(|log| log.try_into_change())
+    grandpa_logs::<H>(digest)
+        .into_iter()
+        .find_map(|log| log.try_into_change())
+}
+
+/// The forced change of the digest together with the median last finalized block number
+pub fn forced_change<H: HeaderT>(
+    digest: &DigestG<H::Hash>,
+) -> Option<(H::Number, ScheduledChange<H::Number>)> {
+    grandpa_logs::<H>(digest)
+        .into_iter()
+        .find_map(|log| log.try_into_forced_change())
+}
+
+/// Drops all but the latest `retention` items and returns how many were dropped
+fn retain_latest<T>(items: &mut Vec<T>, retention: usize) -> usize {
+    let num_pruned = items.len().saturating_sub(retention);
+    items.drain(..num_pruned);
+    num_pruned
 }
 
 impl fmt::Debug for LightValidation {
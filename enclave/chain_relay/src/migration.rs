@@ -0,0 +1,123 @@
+//! Chain relay states sealed by previous versions of the enclave and their migration.
+
+use crate::std::collections::BTreeMap;
+use crate::std::vec::Vec;
+
+use codec::{Decode, Encode};
+use log::*;
+use sp_finality_grandpa::{AuthorityList, SetId};
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
+use sp_runtime::OpaqueExtrinsic;
+
+use crate::state::{Checkpoint, RelayState, ScheduledChangeAtBlock};
+use crate::{Block, LightValidation, RelayId};
+
+/// `LightValidation` without retention window, whose relays keep all header hashes since genesis
+#[derive(Encode, Decode, Clone, Default)]
+pub struct LightValidationV0 {
+    pub num_relays: RelayId,
+    pub tracked_relays: BTreeMap<RelayId, RelayStateV0<Block>>,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct RelayStateV0<Block: BlockT> {
+    pub last_finalized_block_header: Block::Header,
+    pub current_validator_set: AuthorityList,
+    pub current_validator_set_id: SetId,
+    pub header_hashes: Vec<Block::Hash>,
+    pub unjustified_headers: Vec<Block::Hash>,
+    pub verify_tx_inclusion: Vec<OpaqueExtrinsic>,
+    pub scheduled_change: Option<ScheduledChangeAtBlock<Block::Header>>,
+}
+
+/// `LightValidation` whose relays await the inclusion of extrinsics without their calls
+#[derive(Encode, Decode, Clone, Default)]
+pub struct LightValidationV1 {
+    pub num_relays: RelayId,
+    pub tracked_relays: BTreeMap<RelayId, RelayStateV1<Block>>,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct RelayStateV1<Block: BlockT> {
+    pub genesis_hash: Block::Hash,
+    pub last_finalized_block_header: Block::Header,
+    pub current_validator_set: AuthorityList,
+    pub current_validator_set_id: SetId,
+    pub header_hashes: Vec<Block::Hash>,
+    pub checkpoints: Vec<Checkpoint<Block::Header>>,
+    pub unjustified_headers: Vec<Block::Hash>,
+    pub verify_tx_inclusion: Vec<OpaqueExtrinsic>,
+    pub scheduled_change: Option<ScheduledChangeAtBlock<Block::Header>>,
+}
+
+impl From<LightValidationV0> for LightValidationV1 {
+    fn from(validation: LightValidationV0) -> Self {
+        LightValidationV1 {
+            num_relays: validation.num_relays,
+            tracked_relays: validation
+                .tracked_relays
+                .into_iter()
+                .map(|(relay_id, relay)| (relay_id, relay.into()))
+                .collect(),
+        }
+    }
+}
+
+impl<Block: BlockT> From<RelayStateV0<Block>> for RelayStateV1<Block> {
+    fn from(relay: RelayStateV0<Block>) -> Self {
+        // no validator set changes have been recorded, so the latest finalized header is the only checkpoint
+        let last_finalized = &relay.last_finalized_block_header;
+        RelayStateV1 {
+            genesis_hash: relay.header_hashes[0],
+            last_finalized_block_header: last_finalized.clone(),
+            current_validator_set: relay.current_validator_set,
+            current_validator_set_id: relay.current_validator_set_id,
+            header_hashes: relay.header_hashes,
+            checkpoints: vec![Checkpoint {
+                number: *last_finalized.number(),
+                hash: last_finalized.hash(),
+                validator_set_id: relay.current_validator_set_id,
+            }],
+            unjustified_headers: relay.unjustified_headers,
+            verify_tx_inclusion: relay.verify_tx_inclusion,
+            scheduled_change: relay.scheduled_change,
+        }
+    }
+}
+
+impl From<LightValidationV1> for LightValidation {
+    fn from(validation: LightValidationV1) -> Self {
+        LightValidation {
+            num_relays: validation.num_relays,
+            tracked_relays: validation
+                .tracked_relays
+                .into_iter()
+                .map(|(relay_id, relay)| (relay_id, relay.into()))
+                .collect(),
+        }
+    }
+}
+
+impl<Block: BlockT> From<RelayStateV1<Block>> for RelayState<Block> {
+    fn from(relay: RelayStateV1<Block>) -> Self {
+        if !relay.verify_tx_inclusion.is_empty() {
+            // the extrinsics cannot be composed again without their calls
+            warn!(
+                "Dropping {} extrinsics awaiting inclusion",
+                relay.verify_tx_inclusion.len()
+            );
+        }
+        RelayState {
+            genesis_hash: relay.genesis_hash,
+            last_finalized_block_header: relay.last_finalized_block_header,
+            current_validator_set: relay.current_validator_set,
+            current_validator_set_id: relay.current_validator_set_id,
+            header_hashes: relay.header_hashes,
+            checkpoints: relay.checkpoints,
+            unjustified_headers: relay.unjustified_headers,
+            verify_tx_inclusion: Vec::new(),
+            num_stuck_extrinsics: 0,
+            scheduled_change: relay.scheduled_change,
+        }
+    }
+}
@@ -7,12 +7,15 @@ use std::vec::Vec;
 
 #[derive(Encode, Decode, Clone, PartialEq)]
 pub struct RelayState<Block: BlockT> {
+    pub genesis_hash: Block::Hash,
     pub last_finalized_block_header: Block::Header,
     pub current_validator_set: AuthorityList,
     pub current_validator_set_id: SetId,
-    pub header_hashes: Vec<Block::Hash>,
+    pub header_hashes: Vec<Block::Hash>, // Latest finalized headers, pruned to the retention window
+    pub checkpoints: Vec<Checkpoint<Block::Header>>, // First justified header of each validator set
     pub unjustified_headers: Vec<Block::Hash>, // Finalized headers without grandpa proof
-    pub verify_tx_inclusion: Vec<OpaqueExtrinsic>, // Transactions sent by the relay
+    pub verify_tx_inclusion: Vec<PendingExtrinsic<Block::Header>>, // Transactions sent by the relay
+    pub num_stuck_extrinsics: u64,       // Transactions reported as not included in time
     pub scheduled_change: Option<ScheduledChangeAtBlock<Block::Header>>, // Scheduled Authorities change as indicated in the header's digest.
 }
 
@@ -22,15 +25,55 @@ pub struct ScheduledChangeAtBlock<Header: HeaderT> {
     pub next_authority_list: AuthorityList,
 }
 
+/// Extrinsic sent to the chain whose inclusion has not been seen yet
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct PendingExtrinsic<Header: HeaderT> {
+    pub extrinsic: OpaqueExtrinsic,
+    /// Encoded call of the extrinsic, to compose it again if it is not included
+    pub call: Vec<u8>,
+    /// Latest finalized block at the time the extrinsic was sent
+    pub submitted_at: Header::Number,
+}
+
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct Checkpoint<Header: HeaderT> {
+    pub number: Header::Number,
+    pub hash: Header::Hash,
+    pub validator_set_id: SetId,
+}
+
+/// Authority set change as stored by the grandpa pallet until it is enacted
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct StoredPendingChange<N> {
+    pub scheduled_at: N,
+    pub delay: N,
+    pub next_authorities: AuthorityList,
+    pub forced: Option<N>,
+}
+
 impl<Block: BlockT> RelayState<Block> {
-    pub fn new(block_header: Block::Header, validator_set: AuthorityList) -> Self {
+    /// Creates a relay following the chain from the trusted `block_header` on.
+    /// This does not need to be the genesis header of the chain.
+    pub fn new(
+        genesis_hash: Block::Hash,
+        block_header: Block::Header,
+        validator_set: AuthorityList,
+        validator_set_id: SetId,
+    ) -> Self {
         RelayState {
+            genesis_hash,
             last_finalized_block_header: block_header.clone(),
             current_validator_set: validator_set,
-            current_validator_set_id: 0,
+            current_validator_set_id: validator_set_id,
             header_hashes: vec![block_header.hash()],
+            checkpoints: vec![Checkpoint {
+                number: *block_header.number(),
+                hash: block_header.hash(),
+                validator_set_id,
+            }],
             unjustified_headers: Vec::new(),
             verify_tx_inclusion: Vec::new(),
+            num_stuck_extrinsics: 0,
             scheduled_change: None,
         }
     }
@@ -41,11 +84,15 @@ impl<Block: BlockT> fmt::Debug for RelayState<Block> {
         write!(
             f,
             "RelayInfo {{ last_finalized_block_header_number: {:?}, current_validator_set: {:?}, \
-        current_validator_set_id: {} amount of transaction in tx_inclusion_queue: {} }}",
+        current_validator_set_id: {} amount of header hashes: {} amount of checkpoints: {} \
+        amount of transaction in tx_inclusion_queue: {} amount of stuck transactions: {} }}",
             self.last_finalized_block_header.number(),
             self.current_validator_set,
             self.current_validator_set_id,
-            self.verify_tx_inclusion.len()
+            self.header_hashes.len(),
+            self.checkpoints.len(),
+            self.verify_tx_inclusion.len(),
+            self.num_stuck_extrinsics
         )
     }
 }
@@ -18,7 +18,10 @@
 
 use crate::std::vec::Vec;
 use hash_db::{HashDB, Hasher, EMPTY_PREFIX};
-use sp_trie::{trie_types::TrieDB, MemoryDB, Trie};
+use sp_trie::{
+    trie_types::{TrieDB, TrieDBMut},
+    MemoryDB, Trie, TrieMut,
+};
 
 use super::Error;
 
@@ -77,6 +80,23 @@ where
     }
 }
 
+/// Builds a trie of the entries and returns its root along with a proof of all of them
+///
+/// Meant for tests, the proofs of a live chain are fetched from its node.
+pub fn create_storage_proof<H: Hasher>(entries: Vec<(Vec<u8>, Vec<u8>)>) -> (H::Out, StorageProof) {
+    let mut db = MemoryDB::<H>::default();
+    let mut root = Default::default();
+    {
+        let mut trie = TrieDBMut::<H>::new(&mut db, &mut root);
+        for (key, value) in entries.iter() {
+            trie.insert(key, value)
+                .expect("inserting into an in-memory trie does not fail");
+        }
+    }
+    let proof = db.drain().into_iter().map(|(_, (node, _))| node).collect();
+    (root, proof)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
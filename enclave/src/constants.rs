@@ -17,6 +17,7 @@
 pub const RSA3072_SEALED_KEY_FILE: &str = "rsa3072_key_sealed.bin";
 pub const SEALED_SIGNER_SEED_FILE: &str = "ed25519_key_sealed.bin";
 pub const ENCRYPTED_STATE_FILE: &str = "state.bin";
+pub const SEALED_GENESIS_CONFIG_FILE: &str = "genesis_sealed.bin";
 pub const SHARDS_PATH: &str = "./shards";
 pub const AES_KEY_FILE_AND_INIT_V: &str = "aes_key_sealed.bin";
 pub const CHAIN_RELAY_DB: &str = "chain_relay_db.bin";
@@ -46,7 +47,3 @@ pub static SHIELD_FUNDS: u8 = 5u8;
 // bump this to be consistent with SubstraTEE-node runtime
 pub static RUNTIME_SPEC_VERSION: u32 = 100;
 pub static RUNTIME_TRANSACTION_VERSION: u32 = 1;
-
-// timeouts for getter and call execution
-pub static CALLTIMEOUT: i64 = 300; // timeout in ms
-pub static GETTERTIMEOUT: i64 = 300; // timeout in ms
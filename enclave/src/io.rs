@@ -76,18 +76,28 @@ fn _write<F: Write>(bytes: &[u8], mut file: F) -> SgxResult<sgx_status_t> {
 pub mod light_validation {
     use crate::constants::CHAIN_RELAY_DB;
     use crate::utils::UnwrapOrSgxErrorUnexpected;
+    use chain_relay::migration::{LightValidationV0, LightValidationV1};
     use chain_relay::storage_proof::StorageProof;
-    use chain_relay::{Header, LightValidation};
+    use chain_relay::{Header, LightValidation, RelayId};
     use codec::{Decode, Encode};
     use log::*;
     use sgx_types::{sgx_status_t, SgxResult};
-    use sp_finality_grandpa::VersionedAuthorityList;
+    use sp_core::H256;
+    use sp_finality_grandpa::{SetId, VersionedAuthorityList};
     use std::fs;
     use std::sgxfs::SgxFile;
+    use std::vec::Vec;
+
+    /// Prefix of the versioned chain relay db. The db of version 0 has no prefix.
+    const CHAIN_RELAY_DB_MAGIC: [u8; 4] = *b"crdb";
+    const CHAIN_RELAY_DB_VERSION: u8 = 2;
 
     pub fn unseal() -> SgxResult<LightValidation> {
         let vec = super::unseal(CHAIN_RELAY_DB)?;
-        LightValidation::decode(&mut vec.as_slice()).map_err(|_| sgx_status_t::SGX_ERROR_UNEXPECTED)
+        decode(&vec).map_err(|e| {
+            error!("Decoding chain relay db failed. Error: {:?}", e);
+            sgx_status_t::SGX_ERROR_UNEXPECTED
+        })
     }
 
     pub fn seal(validator: LightValidation) -> SgxResult<sgx_status_t> {
@@ -96,53 +106,76 @@ pub mod light_validation {
             warn!("could not backup previous chain relay state");
         };
         debug!("Seal Chain Relay State. Current state: {:?}", validator);
-        super::seal(validator.encode().as_slice(), CHAIN_RELAY_DB)
+        super::seal(encode(&validator).as_slice(), CHAIN_RELAY_DB)
+    }
+
+    /// Encodes the validator as db of the current version
+    pub fn encode(validator: &LightValidation) -> Vec<u8> {
+        (CHAIN_RELAY_DB_MAGIC, CHAIN_RELAY_DB_VERSION, validator).encode()
     }
 
+    /// Decodes a db of the current version or migrates one of a previous version
+    pub fn decode(mut db: &[u8]) -> Result<LightValidation, codec::Error> {
+        if !db.starts_with(&CHAIN_RELAY_DB_MAGIC) {
+            info!("Migrating chain relay db of version 0");
+            return LightValidationV0::decode(&mut db)
+                .map(LightValidationV1::from)
+                .map(Into::into);
+        }
+        let (_, version) = <([u8; 4], u8)>::decode(&mut db)?;
+        match version {
+            1 => {
+                info!("Migrating chain relay db of version 1");
+                LightValidationV1::decode(&mut db).map(Into::into)
+            }
+            CHAIN_RELAY_DB_VERSION => LightValidation::decode(&mut db),
+            _ => Err("Unknown chain relay db version".into()),
+        }
+    }
+
+    /// Returns the relay tracking the chain with the given genesis hash and its latest
+    /// finalized header. The relay is initialized from the header if the chain is not tracked yet.
     pub fn read_or_init_validator(
+        genesis_hash: H256,
         header: Header,
         auth: VersionedAuthorityList,
+        auth_set_id: SetId,
         proof: StorageProof,
-    ) -> SgxResult<Header> {
-        if SgxFile::open(CHAIN_RELAY_DB).is_err() {
+    ) -> SgxResult<(RelayId, Header)> {
+        let mut validator = if SgxFile::open(CHAIN_RELAY_DB).is_err() {
             info!(
                 "[Enclave] ChainRelay DB not found, creating new! {}",
                 CHAIN_RELAY_DB
             );
-            return init_validator(header, auth, proof);
-        }
-
-        let validator = unseal().sgx_error_with_log("Error reading validator")?;
+            LightValidation::new()
+        } else {
+            unseal().sgx_error_with_log("Error reading validator")?
+        };
 
-        let genesis = validator.genesis_hash(validator.num_relays).unwrap();
-        if genesis == header.hash() {
+        if let Some(relay_id) = validator.relay_id(&genesis_hash) {
             info!(
-                "Found already initialized chain relay with Genesis Hash: {:?}",
-                genesis
+                "Found already initialized relay {} with Genesis Hash: {:?}",
+                relay_id, genesis_hash
             );
             info!("Chain Relay state: {:?}", validator);
-            Ok(validator
-                .latest_finalized_header(validator.num_relays)
-                .unwrap())
-        } else {
-            init_validator(header, auth, proof)
+            return Ok((
+                relay_id,
+                validator.latest_finalized_header(relay_id).unwrap(),
+            ));
         }
-    }
-
-    fn init_validator(
-        header: Header,
-        auth: VersionedAuthorityList,
-        proof: StorageProof,
-    ) -> SgxResult<Header> {
-        let mut validator = LightValidation::new();
 
-        validator
-            .initialize_relay(header, auth.into(), proof)
+        info!(
+            "[Enclave] Initializing relay with Genesis Hash {:?} from block {}",
+            genesis_hash, header.number
+        );
+        let relay_id = validator
+            .initialize_relay(genesis_hash, header, auth.into(), auth_set_id, proof)
             .sgx_error()?;
-        super::seal(validator.encode().as_slice(), CHAIN_RELAY_DB)?;
+        super::seal(encode(&validator).as_slice(), CHAIN_RELAY_DB)?;
 
-        Ok(validator
-            .latest_finalized_header(validator.num_relays)
-            .unwrap())
+        Ok((
+            relay_id,
+            validator.latest_finalized_header(relay_id).unwrap(),
+        ))
     }
 }
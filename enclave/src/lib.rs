@@ -39,22 +39,28 @@ use substratee_node_primitives::{CallWorkerFn, ShieldFundsFn};
 use substratee_worker_primitives::block::{
     Block as SidechainBlock, SignedBlock as SignedSidechainBlock, StatePayload,
 };
-use substratee_worker_primitives::BlockHash;
+use substratee_worker_primitives::{BlockHash, BlockProductionConfig};
 
 use codec::{Decode, Encode};
-use sp_core::{blake2_256, crypto::Pair, H256};
+use sp_core::{
+    blake2_256,
+    crypto::{AccountId32, Pair},
+    H256,
+};
 use sp_finality_grandpa::VersionedAuthorityList;
 
 use constants::{
-    BLOCK_CONFIRMED, CALLTIMEOUT, CALL_CONFIRMED, GETTERTIMEOUT, RUNTIME_SPEC_VERSION,
-    RUNTIME_TRANSACTION_VERSION, SUBSRATEE_REGISTRY_MODULE,
+    BLOCK_CONFIRMED, CALL_CONFIRMED, RUNTIME_SPEC_VERSION, RUNTIME_TRANSACTION_VERSION,
+    SUBSRATEE_REGISTRY_MODULE,
 };
 
 use std::slice;
+use std::string::{String, ToString};
 use std::vec::Vec;
 
 use core::ops::Deref;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::sync::{SgxMutex, SgxMutexGuard};
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -65,21 +71,28 @@ use crate::constants::{CALL_WORKER, SHIELD_FUNDS};
 use crate::utils::UnwrapOrSgxErrorUnexpected;
 use chain_relay::{
     storage_proof::{StorageProof, StorageProofChecker},
-    Block, Header, LightValidation,
+    Block, Header, LightValidation, RelayId,
 };
 use sp_runtime::OpaqueExtrinsic;
-use sp_runtime::{generic::SignedBlock, traits::Header as HeaderT};
+use sp_runtime::{
+    generic::{Era, SignedBlock},
+    traits::Header as HeaderT,
+};
 use substrate_api_client::extrinsic::xt_primitives::UncheckedExtrinsicV4;
 
 use sgx_externalities::SgxExternalitiesTypeTrait;
-use substratee_stf::sgx::{shards_key_hash, storage_hashes_to_update_per_shard, OpaqueCall};
+use substratee_stf::sgx::{
+    shards_key_hash, storage_hashes_to_update_per_shard, storage_map_key, OpaqueCall, StorageHasher,
+};
 use substratee_stf::State as StfState;
+use substratee_stf::StateTypeDiff as StfStateTypeDiff;
 use substratee_stf::{
-    AccountId, Getter, ShardIdentifier, Stf, TrustedCall, TrustedCallSigned, TrustedGetterSigned,
+    AccountId, GenesisConfig, Getter, ShardIdentifier, Stf, TrustedCall, TrustedCallSigned,
+    TrustedOperation, NATIVE_ASSET_ID,
 };
 
 use rpc::author::{hash::TrustedOperationOrHash, Author, AuthorApi};
-use rpc::worker_api_direct;
+use rpc::worker_api_direct::send_operation_events;
 use rpc::{api::SideChainApi, basic_pool::BasicPool};
 
 mod aes;
@@ -104,12 +117,18 @@ pub const CERTEXPIRYDAYS: i64 = 90i64;
 #[derive(Debug, Clone, PartialEq)]
 pub enum Timeout {
     Call,
-    Getter,
 }
 
 pub type Hash = sp_core::H256;
 type BPool = BasicPool<SideChainApi<Block>, Block>;
 
+static BLOCK_PRODUCTION_CONFIG: AtomicPtr<()> = AtomicPtr::new(0 as *mut ());
+/// Relay of the chain the sidechain blocks are confirmed on, set upon its initialization
+static LAYER_ONE_RELAY_ID: AtomicU64 = AtomicU64::new(0);
+/// Nonce following the extrinsics the enclave sent to the chain the sidechain blocks are
+/// confirmed on
+static NEXT_XT_NONCE: AtomicU32 = AtomicU32::new(0);
+
 #[no_mangle]
 pub unsafe extern "C" fn init() -> sgx_status_t {
     // initialize the logging environment in the enclave
@@ -194,25 +213,49 @@ pub unsafe extern "C" fn get_ecc_signing_pubkey(pubkey: *mut u8, pubkey_size: u3
     sgx_status_t::SGX_SUCCESS
 }
 
+/// Number of finalized blocks for which an extrinsic of the enclave is valid
+///
+/// The inclusion timeout is rounded up to a power of two, as required by mortal eras.
+/// Up to 4096 blocks, the era of an extrinsic starts at the block it is composed at.
+pub fn xt_mortality() -> u32 {
+    block_production_config()
+        .xt_inclusion_timeout
+        .next_power_of_two()
+        .max(4)
+        .min(4096)
+}
+
+/// Composes the extrinsics of the calls for the chain of the relay
+///
+/// The extrinsics are mortal, so that an extrinsic which has not been included
+/// within `xt_mortality` blocks can never be included and is safe to send again.
 fn create_extrinsics(
-    validator: LightValidation,
-    calls_buffer: Vec<OpaqueCall>,
+    validator: &LightValidation,
+    relay_id: RelayId,
+    calls_buffer: &[OpaqueCall],
     mut nonce: u32,
 ) -> SgxResult<Vec<Vec<u8>>> {
     // get information for composing the extrinsic
     let signer = ed25519::unseal_pair()?;
     debug!("Restored ECC pubkey: {:?}", signer.public());
+    let genesis_hash = validator
+        .genesis_hash(relay_id)
+        .sgx_error_with_log("Unknown relay")?;
+    let checkpoint = validator
+        .latest_finalized_header(relay_id)
+        .sgx_error_with_log("Unknown relay")?;
+    let era = Era::mortal(xt_mortality() as u64, checkpoint.number as u64);
 
     let extrinsics_buffer: Vec<Vec<u8>> = calls_buffer
-        .into_iter()
+        .iter()
         .map(|call| {
             let xt = compose_extrinsic_offline!(
                 signer.clone(),
-                call,
+                call.clone(),
                 nonce,
-                Era::Immortal,
-                validator.genesis_hash(validator.num_relays).unwrap(),
-                validator.genesis_hash(validator.num_relays).unwrap(),
+                era,
+                genesis_hash,
+                checkpoint.hash(),
                 RUNTIME_SPEC_VERSION,
                 RUNTIME_TRANSACTION_VERSION
             )
@@ -225,6 +268,71 @@ fn create_extrinsics(
     Ok(extrinsics_buffer)
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn set_block_production_config(
+    config: *const u8,
+    config_size: usize,
+) -> sgx_status_t {
+    let mut config_slice = slice::from_raw_parts(config, config_size);
+    let config = match BlockProductionConfig::decode(&mut config_slice) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Decoding block production config failed. Error: {:?}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
+    info!("Setting block production config: {:?}", config);
+    let config_ptr = Arc::into_raw(Arc::new(config));
+    BLOCK_PRODUCTION_CONFIG.store(config_ptr as *mut (), Ordering::SeqCst);
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Returns the relay of the chain the sidechain blocks are confirmed on.
+/// Relay ids start at 1, so 0 means the relay has not been initialized yet.
+pub fn layer_one_relay_id() -> RelayId {
+    LAYER_ONE_RELAY_ID.load(Ordering::SeqCst)
+}
+
+/// Returns the block production config set by the worker, or the default one
+pub fn block_production_config() -> BlockProductionConfig {
+    let ptr = BLOCK_PRODUCTION_CONFIG.load(Ordering::SeqCst) as *mut BlockProductionConfig;
+    if ptr.is_null() {
+        BlockProductionConfig::default()
+    } else {
+        unsafe { (*ptr).clone() }
+    }
+}
+
+/// Initializes the shard with the genesis config supplied by the operator
+#[no_mangle]
+pub unsafe extern "C" fn init_shard(
+    shard: *const u8,
+    shard_size: u32,
+    genesis_config: *const u8,
+    genesis_config_size: usize,
+) -> sgx_status_t {
+    let shard = ShardIdentifier::from_slice(slice::from_raw_parts(shard, shard_size as usize));
+    let mut genesis_config_slice = slice::from_raw_parts(genesis_config, genesis_config_size);
+    let genesis_config = match GenesisConfig::decode(&mut genesis_config_slice) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("Decoding genesis config failed. Error: {:?}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
+    info!(
+        "Initializing shard {} with genesis config: {:?}",
+        shard.encode().to_base58(),
+        genesis_config
+    );
+    if let Err(e) = state::init_shard_with_genesis(&shard, &genesis_config) {
+        return e;
+    }
+
+    sgx_status_t::SGX_SUCCESS
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn get_state(
     trusted_op: *const u8,
@@ -264,9 +372,13 @@ pub unsafe extern "C" fn get_state(
         Err(e) => return e,
     };
 
-    let latest_header = validator
-        .latest_finalized_header(validator.num_relays)
-        .unwrap();
+    let latest_header = match validator.latest_finalized_header(layer_one_relay_id()) {
+        Ok(h) => h,
+        Err(e) => {
+            error!("Chain relay is not synced yet. Error: {:?}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
 
     // FIXME: not sure we will ever need this as we are querying trusted state, not onchain state
     // i.e. demurrage could be correctly applied with this, but the client could do that too.
@@ -299,24 +411,41 @@ pub unsafe extern "C" fn get_state(
     sgx_status_t::SGX_SUCCESS
 }
 
+/// Initializes the chain relay from a trusted finalized header, unless it already follows the chain
+///
+/// `layer_one` marks the chain the sidechain blocks are confirmed on.
 #[no_mangle]
 pub unsafe extern "C" fn init_chain_relay(
-    genesis_header: *const u8,
-    genesis_header_size: usize,
+    genesis_hash: *const u8,
+    genesis_hash_size: usize,
+    header: *const u8,
+    header_size: usize,
     authority_list: *const u8,
     authority_list_size: usize,
+    authority_set_id: u64,
     authority_proof: *const u8,
     authority_proof_size: usize,
+    layer_one: u8,
+    relay_id: *mut u64,
     latest_header: *mut u8,
     latest_header_size: usize,
 ) -> sgx_status_t {
     info!("Initializing Chain Relay!");
 
-    let mut header = slice::from_raw_parts(genesis_header, genesis_header_size);
+    let mut genesis_hash = slice::from_raw_parts(genesis_hash, genesis_hash_size);
+    let mut header = slice::from_raw_parts(header, header_size);
     let latest_header_slice = slice::from_raw_parts_mut(latest_header, latest_header_size);
     let mut auth = slice::from_raw_parts(authority_list, authority_list_size);
     let mut proof = slice::from_raw_parts(authority_proof, authority_proof_size);
 
+    let genesis_hash = match H256::decode(&mut genesis_hash) {
+        Ok(h) => h,
+        Err(e) => {
+            error!("Decoding genesis hash failed. Error: {:?}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
+
     let header = match Header::decode(&mut header) {
         Ok(h) => h,
         Err(e) => {
@@ -341,27 +470,39 @@ pub unsafe extern "C" fn init_chain_relay(
         }
     };
 
-    match io::light_validation::read_or_init_validator(header, auth, proof) {
-        Ok(header) => write_slice_and_whitespace_pad(latest_header_slice, header.encode()),
+    match io::light_validation::read_or_init_validator(
+        genesis_hash,
+        header,
+        auth,
+        authority_set_id,
+        proof,
+    ) {
+        Ok((id, header)) => {
+            if layer_one != 0 {
+                LAYER_ONE_RELAY_ID.store(id, Ordering::SeqCst);
+            }
+            *relay_id = id;
+            write_slice_and_whitespace_pad(latest_header_slice, header.encode())
+        }
         Err(e) => return e,
     }
     sgx_status_t::SGX_SUCCESS
 }
 
+/// Syncs the relay of the chain the sidechain blocks are confirmed on
+/// and produces the sidechain blocks of the shards the enclave is the designated worker for.
+///
+/// `nonce` is the nonce of the enclave account on that chain.
 #[no_mangle]
 pub unsafe extern "C" fn produce_blocks(
+    relay_id: u64,
     blocks_to_sync: *const u8,
     blocks_to_sync_size: usize,
     nonce: *const u32,
 ) -> sgx_status_t {
-    let mut blocks_to_sync_slice = slice::from_raw_parts(blocks_to_sync, blocks_to_sync_size);
-
-    let blocks_to_sync: Vec<SignedBlock<Block>> = match Decode::decode(&mut blocks_to_sync_slice) {
+    let blocks_to_sync = match decode_blocks_to_sync(blocks_to_sync, blocks_to_sync_size) {
         Ok(b) => b,
-        Err(e) => {
-            error!("Decoding signed blocks failed. Error: {:?}", e);
-            return sgx_status_t::SGX_ERROR_UNEXPECTED;
-        }
+        Err(e) => return e,
     };
 
     let mut validator = match io::light_validation::unseal() {
@@ -371,46 +512,45 @@ pub unsafe extern "C" fn produce_blocks(
 
     let mut calls = Vec::<OpaqueCall>::new();
 
-    debug!("Syncing chain relay!");
-    if !blocks_to_sync.is_empty() {
-        for signed_block in blocks_to_sync.into_iter() {
-            validator
-                .check_xt_inclusion(validator.num_relays, &signed_block.block)
-                .unwrap(); // panic can only happen if relay_id does not exist
-            if let Err(e) = validator.submit_simple_header(
-                validator.num_relays,
-                signed_block.block.header.clone(),
-                signed_block.justifications.clone(),
-            ) {
-                error!("Block verification failed. Error : {:?}", e);
-                return sgx_status_t::SGX_ERROR_UNEXPECTED;
-            }
-
-            if update_states(signed_block.block.header.clone()).is_err() {
-                error!("Error performing state updates upon block import");
-                return sgx_status_t::SGX_ERROR_UNEXPECTED;
-            }
+    debug!("Syncing chain relay {}!", relay_id);
+    for signed_block in blocks_to_sync.into_iter() {
+        if let Err(e) = import_block(&mut validator, relay_id, &signed_block) {
+            return e;
+        }
 
-            // execute indirect calls, incl. shielding and unshielding
-            match scan_block_for_relevant_xt(&signed_block.block) {
-                // push shield funds to opaque calls
-                Ok(c) => calls.extend(c.into_iter()),
-                Err(_) => error!("Error executing relevant extrinsics"),
-            };
-            // compose indirect block confirmation
-            let xt_block = [SUBSRATEE_REGISTRY_MODULE, BLOCK_CONFIRMED];
-            let genesis_hash = validator.genesis_hash(validator.num_relays).unwrap();
-            let block_hash = signed_block.block.header.hash();
-            let prev_state_hash = signed_block.block.header.parent_hash();
-            calls.push(OpaqueCall(
-                (xt_block, genesis_hash, block_hash, prev_state_hash.encode()).encode(),
-            ));
+        if update_states(signed_block.block.header.clone()).is_err() {
+            error!("Error performing state updates upon block import");
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
         }
+
+        // execute indirect calls, incl. shielding and unshielding
+        match scan_block_for_relevant_xt(&signed_block.block) {
+            // push shield funds to opaque calls
+            Ok(c) => calls.extend(c.into_iter()),
+            Err(_) => error!("Error executing relevant extrinsics"),
+        };
+        // compose indirect block confirmation
+        let xt_block = [SUBSRATEE_REGISTRY_MODULE, BLOCK_CONFIRMED];
+        let genesis_hash = validator.genesis_hash(relay_id).unwrap();
+        let block_hash = signed_block.block.header.hash();
+        let prev_state_hash = signed_block.block.header.parent_hash();
+        calls.push(OpaqueCall(
+            (xt_block, genesis_hash, block_hash, prev_state_hash.encode()).encode(),
+        ));
     }
+
+    // send the extrinsics again which can no longer be included, ahead of the new ones and
+    // starting at the nonce of the chain
+    let mut stuck = match take_stuck_calls(&mut validator, relay_id) {
+        Ok(stuck) => stuck,
+        Err(e) => return e,
+    };
+    let nonce = next_xt_nonce(*nonce, !stuck.is_empty());
+    stuck.append(&mut calls);
+    let mut calls = stuck;
+
     // get header of last block
-    let latest_onchain_header: Header = validator
-        .latest_finalized_header(validator.num_relays)
-        .unwrap();
+    let latest_onchain_header: Header = validator.latest_finalized_header(relay_id).unwrap();
     // execute pending calls from operation pool and create block
     // (one per shard) as opaque call with block confirmation
     let signed_blocks: Vec<SignedSidechainBlock> =
@@ -422,33 +562,351 @@ pub unsafe extern "C" fn produce_blocks(
             Err(_) => return sgx_status_t::SGX_ERROR_UNEXPECTED,
         };
 
-    let extrinsics = match create_extrinsics(validator.clone(), calls, *nonce) {
+    let extrinsics = match create_extrinsics(&validator, relay_id, &calls, nonce) {
+        Ok(xt) => xt,
+        Err(e) => return e,
+    };
+
+    if let Err(e) = finish_relay_sync(validator, relay_id, relay_id, &calls, &extrinsics) {
+        return e;
+    }
+
+    let sent = extrinsics.len() as u32;
+    // ocall to worker to store signed block and send block confirmation
+    if let Err(_e) = send_block_and_confirmation(extrinsics, signed_blocks) {
+        return sgx_status_t::SGX_ERROR_UNEXPECTED;
+    }
+    NEXT_XT_NONCE.store(nonce + sent, Ordering::SeqCst);
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+/// Syncs the relay of an observed chain, e.g. a bridge chain, and executes its relevant
+/// extrinsics. The resulting extrinsics are sent to the chain of `layer_one_relay_id`,
+/// on which the enclave account has the nonce `nonce`.
+#[no_mangle]
+pub unsafe extern "C" fn sync_chain_relay(
+    relay_id: u64,
+    layer_one_relay_id: u64,
+    blocks_to_sync: *const u8,
+    blocks_to_sync_size: usize,
+    nonce: *const u32,
+) -> sgx_status_t {
+    let blocks_to_sync = match decode_blocks_to_sync(blocks_to_sync, blocks_to_sync_size) {
+        Ok(b) => b,
+        Err(e) => return e,
+    };
+
+    let mut validator = match io::light_validation::unseal() {
+        Ok(v) => v,
+        Err(e) => return e,
+    };
+
+    let mut calls = Vec::<OpaqueCall>::new();
+
+    debug!("Syncing chain relay {}!", relay_id);
+    for signed_block in blocks_to_sync.iter() {
+        if let Err(e) = import_block(&mut validator, relay_id, signed_block) {
+            return e;
+        }
+
+        match scan_block_for_relevant_xt(&signed_block.block) {
+            Ok(c) => calls.extend(c.into_iter()),
+            Err(_) => error!("Error executing relevant extrinsics"),
+        };
+    }
+
+    let nonce = next_xt_nonce(*nonce, false);
+    let extrinsics = match create_extrinsics(&validator, layer_one_relay_id, &calls, nonce) {
         Ok(xt) => xt,
-        Err(_) => return sgx_status_t::SGX_ERROR_UNEXPECTED,
+        Err(e) => return e,
     };
 
-    // store extrinsics in chain relay for finalization check
-    for xt in extrinsics.iter() {
+    if let Err(e) = finish_relay_sync(validator, relay_id, layer_one_relay_id, &calls, &extrinsics)
+    {
+        return e;
+    }
+
+    let sent = extrinsics.len() as u32;
+    if let Err(_e) = send_block_and_confirmation(extrinsics, vec![]) {
+        return sgx_status_t::SGX_ERROR_UNEXPECTED;
+    }
+    NEXT_XT_NONCE.store(nonce + sent, Ordering::SeqCst);
+
+    sgx_status_t::SGX_SUCCESS
+}
+
+unsafe fn decode_blocks_to_sync(
+    blocks_to_sync: *const u8,
+    blocks_to_sync_size: usize,
+) -> SgxResult<Vec<SignedBlock<Block>>> {
+    let mut blocks_to_sync_slice = slice::from_raw_parts(blocks_to_sync, blocks_to_sync_size);
+    Decode::decode(&mut blocks_to_sync_slice).map_err(|e| {
+        error!("Decoding signed blocks failed. Error: {:?}", e);
+        sgx_status_t::SGX_ERROR_UNEXPECTED
+    })
+}
+
+/// Verifies the block with the relay and confirms the inclusion of the extrinsics it awaits
+fn import_block(
+    validator: &mut LightValidation,
+    relay_id: RelayId,
+    signed_block: &SignedBlock<Block>,
+) -> SgxResult<()> {
+    validator
+        .check_xt_inclusion(relay_id, &signed_block.block)
+        .sgx_error_with_log("Unknown relay")?;
+    validator
+        .submit_simple_header(
+            relay_id,
+            signed_block.block.header.clone(),
+            signed_block.justifications.clone(),
+        )
+        .map_err(|e| {
+            error!("Block verification failed. Error : {:?}", e);
+            sgx_status_t::SGX_ERROR_UNEXPECTED
+        })
+}
+
+/// Returns the nonce of the next extrinsic sent to the chain the sidechain blocks are confirmed on
+///
+/// The extrinsics sent before may not be included yet, so the nonce following them is used
+/// if it is ahead of `chain_nonce`. Once an extrinsic is stuck, its nonce has never been used
+/// on chain and no later extrinsic can be included, so the nonce of the chain is used again.
+fn next_xt_nonce(chain_nonce: u32, stuck: bool) -> u32 {
+    if stuck {
+        chain_nonce
+    } else {
+        chain_nonce.max(NEXT_XT_NONCE.load(Ordering::SeqCst))
+    }
+}
+
+/// Takes the calls of the extrinsics whose era ended before they were included
+fn take_stuck_calls(
+    validator: &mut LightValidation,
+    relay_id: RelayId,
+) -> SgxResult<Vec<OpaqueCall>> {
+    let stuck = validator
+        .take_stuck_extrinsics(relay_id, xt_mortality())
+        .sgx_error_with_log("Checking extrinsic inclusion failed")?;
+    Ok(stuck.into_iter().map(|xt| OpaqueCall(xt.call)).collect())
+}
+
+/// Stores the extrinsics sent to the chain of the relay, with their calls, for the finalization check
+fn track_extrinsics(
+    validator: &mut LightValidation,
+    relay_id: RelayId,
+    calls: &[OpaqueCall],
+    extrinsics: &[Vec<u8>],
+) -> SgxResult<()> {
+    for (call, xt) in calls.iter().zip(extrinsics.iter()) {
         validator
             .submit_xt_to_be_included(
-                validator.num_relays,
+                relay_id,
                 OpaqueExtrinsic::from_bytes(xt.as_slice()).unwrap(),
+                call.0.clone(),
             )
-            .unwrap();
+            .sgx_error_with_log("Unknown relay")?;
     }
+    Ok(())
+}
 
-    if io::light_validation::seal(validator).is_err() {
-        return sgx_status_t::SGX_ERROR_UNEXPECTED;
+/// Stores the extrinsics sent to the layer one chain for the finalization check,
+/// prunes the synced relay and seals the chain relay
+fn finish_relay_sync(
+    mut validator: LightValidation,
+    relay_id: RelayId,
+    layer_one_relay_id: RelayId,
+    calls: &[OpaqueCall],
+    extrinsics: &[Vec<u8>],
+) -> SgxResult<()> {
+    track_extrinsics(&mut validator, layer_one_relay_id, calls, extrinsics)?;
+
+    // bound the size of the sealed chain relay
+    let retention = block_production_config().relay_header_retention as usize;
+    validator
+        .prune_relay(relay_id, retention)
+        .sgx_error_with_log("Unknown relay")?;
+
+    io::light_validation::seal(validator)?;
+    Ok(())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn import_sidechain_blocks(
+    blocks_to_import: *const u8,
+    blocks_to_import_size: usize,
+) -> sgx_status_t {
+    let mut blocks_to_import_slice = slice::from_raw_parts(blocks_to_import, blocks_to_import_size);
+
+    let signed_blocks: Vec<SignedSidechainBlock> = match Decode::decode(&mut blocks_to_import_slice)
+    {
+        Ok(b) => b,
+        Err(e) => {
+            error!("Decoding signed sidechain blocks failed. Error: {:?}", e);
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
     };
 
-    // ocall to worker to store signed block and send block confirmation
-    if let Err(_e) = send_block_and_confirmation(extrinsics, signed_blocks) {
-        return sgx_status_t::SGX_ERROR_UNEXPECTED;
+    for signed_block in signed_blocks.into_iter() {
+        if let Err(e) = import_sidechain_block(signed_block) {
+            return e;
+        }
     }
 
     sgx_status_t::SGX_SUCCESS
 }
 
+/// Imports a sidechain block produced by another enclave of the same shard
+///
+/// The block must be signed by an enclave registered on layer one with our MRENCLAVE,
+/// i.e. one sharing the state key of the shard.
+pub fn import_sidechain_block(signed_block: SignedSidechainBlock) -> SgxResult<()> {
+    let block = signed_block.block();
+    debug!(
+        "Importing sidechain block #{} of shard {}",
+        block.block_number(),
+        block.shard_id().encode().to_base58()
+    );
+
+    if !signed_block.verify_signature() {
+        error!("Sidechain block has an invalid signature");
+        return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+    }
+
+    let author = block.block_author();
+    let mrenclave = attestation::get_mrenclave_of_self()?;
+    if !is_enclave_of_shard(author, registered_enclave(author)?, &mrenclave.m) {
+        error!("Author of the sidechain block is not a registered enclave of the shard");
+        return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+    }
+
+    apply_sidechain_block(signed_block)
+}
+
+/// Applies a sidechain block of another enclave to the local shard state
+///
+/// Instead of re-executing the trusted calls, the state diff of the block payload
+/// is applied to the local shard state. The local state must match the apriori
+/// state hash of the block, the resulting state its aposteriori state hash.
+pub fn apply_sidechain_block(signed_block: SignedSidechainBlock) -> SgxResult<()> {
+    let block = signed_block.block();
+    let shard = block.shard_id();
+
+    let mut state = if state::exists(&shard) {
+        state::load(&shard)?
+    } else {
+        state::init_shard(&shard)?;
+        Stf::init_state()
+    };
+
+    let last_block_hash = Stf::get_last_block_hash(&mut state).sgx_error()?;
+    let last_block_number = Stf::get_sidechain_block_number(&mut state).sgx_error()?;
+    if block.parent_hash() != last_block_hash || block.block_number() != last_block_number + 1 {
+        error!(
+            "Sidechain block #{} does not extend the local block #{} of the shard",
+            block.block_number(),
+            last_block_number
+        );
+        return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+    }
+
+    // decrypt the payload with the shared state key
+    let mut payload = block.state_payload().clone();
+    aes::de_or_encrypt(&mut payload)?;
+    let state_payload = StatePayload::decode(&mut payload.as_slice())
+        .sgx_error_with_log("Could not decode state payload")?;
+
+    if state::hash_of(state.state.clone())? != state_payload.state_hash_apriori() {
+        error!("Local shard state does not match the apriori state hash of the block");
+        return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+    }
+
+    let state_diff = StfStateTypeDiff::decode(state_payload.state_update().clone());
+    Stf::update_storage(&mut state, &state_diff);
+
+    if state::hash_of(state.state.clone())? != state_payload.state_hash_aposteriori() {
+        error!("Resulting shard state does not match the aposteriori state hash of the block");
+        return Err(sgx_status_t::SGX_ERROR_UNEXPECTED);
+    }
+
+    // the block producer updates the last block hash after hashing the state
+    Stf::update_last_block_hash(&mut state, block.hash());
+    state::write(state, &shard)?;
+    Ok(())
+}
+
+/// Enclave registered in the SubstrateeRegistry, by its account and MRENCLAVE
+pub type RegisteredEnclave = (AccountId32, [u8; 32]);
+
+/// Returns whether the `registered` enclave is the one of `author` and runs our MRENCLAVE
+pub fn is_enclave_of_shard(
+    author: &AccountId32,
+    registered: Option<RegisteredEnclave>,
+    mrenclave: &[u8; 32],
+) -> bool {
+    match registered {
+        Some((pubkey, mr_enclave)) => pubkey == *author && mr_enclave == *mrenclave,
+        None => false,
+    }
+}
+
+/// Looks up the enclave registered for the account at the latest finalized layer one block
+fn registered_enclave(account: &AccountId32) -> SgxResult<Option<RegisteredEnclave>> {
+    let index_key = storage_map_key(
+        "SubstrateeRegistry",
+        "EnclaveIndex",
+        account,
+        &StorageHasher::Blake2_128Concat,
+    );
+    match layer_one_storage::<u64>(index_key)? {
+        Some(index) => enclave_at_index(index),
+        None => Ok(None),
+    }
+}
+
+/// Looks up the enclave designated to produce the blocks of the shard at the latest finalized
+/// layer one block
+fn worker_for_shard(shard: &ShardIdentifier) -> SgxResult<Option<RegisteredEnclave>> {
+    let index_key = storage_map_key(
+        "SubstrateeRegistry",
+        "WorkerForShard",
+        shard,
+        &StorageHasher::Blake2_128Concat,
+    );
+    match layer_one_storage::<u64>(index_key)? {
+        Some(index) => enclave_at_index(index),
+        None => Ok(None),
+    }
+}
+
+fn enclave_at_index(index: u64) -> SgxResult<Option<RegisteredEnclave>> {
+    let enclave_key = storage_map_key(
+        "SubstrateeRegistry",
+        "EnclaveRegistry",
+        &index,
+        &StorageHasher::Blake2_128Concat,
+    );
+    // a registered enclave starts with its account and MRENCLAVE
+    layer_one_storage::<RegisteredEnclave>(enclave_key)
+}
+
+/// Reads a storage value at the latest finalized layer one block, verified by its storage proof
+fn layer_one_storage<V: Decode>(key: Vec<u8>) -> SgxResult<Option<V>> {
+    let validator = io::light_validation::unseal()?;
+    let header = validator
+        .latest_finalized_header(layer_one_relay_id())
+        .sgx_error_with_log("Chain relay is not initialized")?;
+    let request = WorkerRequest::ChainStorage(key.clone(), Some(header.hash()));
+    let responses: Vec<WorkerResponse<Vec<u8>>> = worker_request(vec![request])?;
+    match verify_worker_responses(responses, header)?.remove(&key) {
+        Some(Some(value)) => Decode::decode(&mut value.as_slice())
+            .map(Some)
+            .sgx_error_with_log("Could not decode layer one storage value"),
+        _ => Ok(None),
+    }
+}
+
 fn send_block_and_confirmation(
     confirmations: Vec<Vec<u8>>,
     signed_blocks: Vec<SignedSidechainBlock>,
@@ -476,36 +934,6 @@ fn send_block_and_confirmation(
     Ok(())
 }
 
-fn get_stf_state(
-    trusted_getter_signed: TrustedGetterSigned,
-    shard: ShardIdentifier,
-) -> Option<Vec<u8>> {
-    debug!("verifying signature of TrustedGetterSigned");
-    if let false = trusted_getter_signed.verify_signature() {
-        error!("bad signature");
-        return None;
-    }
-
-    if !state::exists(&shard) {
-        info!("Initialized new shard that was queried chain: {:?}", shard);
-        if let Err(e) = state::init_shard(&shard) {
-            error!("Error initialising shard {:?} state: Error: {:?}", shard, e);
-            return None;
-        }
-    }
-
-    let mut state = match state::load(&shard) {
-        Ok(s) => s,
-        Err(e) => {
-            error!("Error loading shard {:?}: Error: {:?}", shard, e);
-            return None;
-        }
-    };
-
-    debug!("calling into STF to get state");
-    Stf::get_state(&mut state, trusted_getter_signed.into())
-}
-
 fn execute_top_pool_calls(
     latest_onchain_header: Header,
 ) -> SgxResult<(Vec<OpaqueCall>, Vec<SignedSidechainBlock>)> {
@@ -527,58 +955,29 @@ fn execute_top_pool_calls(
 
         // get all shards
         let shards = state::list_shards()?;
-
-        // Handle trusted getters
-        let start_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        let mut is_done = false;
-        for shard in shards.clone().into_iter() {
-            // retrieve trusted operations from pool
-            let trusted_getters = match author.get_pending_tops_separated(shard) {
-                Ok((_, getters)) => getters,
-                Err(_) => return Err(sgx_status_t::SGX_ERROR_UNEXPECTED),
-            };
-            for trusted_getter_signed in trusted_getters.into_iter() {
-                // get state
-                let value_opt = get_stf_state(trusted_getter_signed.clone(), shard);
-                // get hash
-                let hash_of_getter = author.hash_of(&trusted_getter_signed.into());
-                // let client know of current state
-                if worker_api_direct::send_state(hash_of_getter, value_opt).is_err() {
-                    error!("Could not get state from stf");
-                }
-                // remove getter from pool
-                if let Err(e) = author.remove_top(
-                    vec![TrustedOperationOrHash::Hash(hash_of_getter)],
-                    shard,
-                    false,
-                ) {
-                    error!(
-                        "Error removing trusted operation from top pool: Error: {:?}",
-                        e
-                    );
-                }
-                // Check time
-                if time_is_overdue(Timeout::Getter, start_time) {
-                    is_done = true;
-                    break;
-                }
-            }
-            if is_done {
-                break;
-            }
-        }
+        let self_account: AccountId32 = ed25519::unseal_pair()?.public().into();
+        let mrenclave = attestation::get_mrenclave_of_self()?;
 
         // Handle trusted calls
+        let max_operations_per_block = block_production_config().max_operations_per_block;
         let start_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
-            .as_secs() as i64;
+            .as_millis() as i64;
         let mut is_done = false;
         for shard in shards.into_iter() {
+            // only the enclave designated by the registry produces the blocks of the shard,
+            // the others import them
+            if !is_enclave_of_shard(&self_account, worker_for_shard(&shard)?, &mrenclave.m) {
+                debug!(
+                    "Not the block producer of shard {}",
+                    shard.encode().to_base58()
+                );
+                continue;
+            }
             let mut call_hashes = Vec::<H256>::new();
+            // governance calls are confirmed on chain, with the state they resulted in
+            let mut governance_call_hashes = Vec::<H256>::new();
 
             // load state before executing any calls
             let mut state = if state::exists(&shard) {
@@ -598,7 +997,11 @@ fn execute_top_pool_calls(
             };
             debug!("Got following trusted calls from pool: {:?}", trusted_calls);
             // call execution
-            for trusted_call_signed in trusted_calls.into_iter() {
+            for trusted_call_signed in trusted_calls
+                .into_iter()
+                .take(max_operations_per_block as usize)
+            {
+                let is_governance = matches!(trusted_call_signed.call, TrustedCall::sudo(..));
                 match handle_trusted_worker_call(
                     &mut calls,
                     &mut state,
@@ -608,8 +1011,11 @@ fn execute_top_pool_calls(
                     Some(author.clone()),
                 ) {
                     Ok(hashes) => {
-                        if let Some((_, operation_hash)) = hashes {
-                            call_hashes.push(operation_hash)
+                        if let Some((call_hash, operation_hash)) = hashes {
+                            call_hashes.push(operation_hash);
+                            if is_governance {
+                                governance_call_hashes.push(call_hash);
+                            }
                         }
                     }
                     Err(e) => error!("Error performing worker call: Error: {:?}", e),
@@ -642,7 +1048,12 @@ fn execute_top_pool_calls(
                 Err(e) => error!("Could not compose block confirmation: {:?}", e),
             }
             // save updated state after call executions
-            let _new_state_hash = state::write(state.clone(), &shard)?;
+            let new_state_hash = state::write(state.clone(), &shard)?;
+            calls.extend(
+                governance_call_hashes
+                    .into_iter()
+                    .map(|call_hash| compose_call_confirmation(shard, call_hash, new_state_hash)),
+            );
 
             if is_done {
                 break;
@@ -653,18 +1064,19 @@ fn execute_top_pool_calls(
     Ok((calls, blocks))
 }
 
-/// Checks if the time of call execution or getter is overdue
+/// Checks if the time of call execution is overdue
 /// Returns true if specified time is exceeded
+/// `start_time` is expected in ms since unix epoch
 pub fn time_is_overdue(timeout: Timeout, start_time: i64) -> bool {
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_secs() as i64;
-    let max_time_ms: i64 = match timeout {
-        Timeout::Call => CALLTIMEOUT,
-        Timeout::Getter => GETTERTIMEOUT,
-    };
-    (now - start_time) * 1000 >= max_time_ms
+        .as_millis() as i64;
+    let config = block_production_config();
+    let max_time_ms = match timeout {
+        Timeout::Call => config.call_timeout,
+    } as i64;
+    now - start_time >= max_time_ms
 }
 
 /// Composes a sidechain block of a shard
@@ -802,8 +1214,10 @@ pub fn scan_block_for_relevant_xt(block: &Block) -> SgxResult<Vec<OpaqueCall>> {
                         state::init_shard(&shard)?;
                         Stf::init_state()
                     };
+                    let is_governance =
+                        matches!(decrypted_trusted_call.call, TrustedCall::sudo(..));
                     // call execution
-                    if let Err(e) = handle_trusted_worker_call(
+                    let executed = match handle_trusted_worker_call(
                         &mut opaque_calls, // necessary for unshielding
                         &mut state,
                         decrypted_trusted_call,
@@ -811,10 +1225,17 @@ pub fn scan_block_for_relevant_xt(block: &Block) -> SgxResult<Vec<OpaqueCall>> {
                         shard,
                         None,
                     ) {
-                        error!("Error performing worker call: Error: {:?}", e);
-                    }
+                        Ok(hashes) => hashes,
+                        Err(e) => {
+                            error!("Error performing worker call: Error: {:?}", e);
+                            None
+                        }
+                    };
                     // save updated state
-                    state::write(state, &shard)?;
+                    let state_hash = state::write(state, &shard)?;
+                    if let (true, Some((call_hash, _))) = (is_governance, executed) {
+                        opaque_calls.push(compose_call_confirmation(shard, call_hash, state_hash));
+                    }
                 }
             }
         }
@@ -827,9 +1248,9 @@ fn handle_shield_funds_xt(
     calls: &mut Vec<OpaqueCall>,
     xt: UncheckedExtrinsicV4<ShieldFundsFn>,
 ) -> SgxResult<()> {
-    let (call, account_encrypted, amount, shard) = xt.function.clone();
-    info!("Found ShieldFunds extrinsic in block: \nCall: {:?} \nAccount Encrypted {:?} \nAmount: {} \nShard: {}",
-        call, account_encrypted, amount, shard.encode().to_base58(),
+    let (call, account_encrypted, asset_id, amount, shard) = xt.function.clone();
+    info!("Found ShieldFunds extrinsic in block: \nCall: {:?} \nAccount Encrypted {:?} \nAsset: {} \nAmount: {} \nShard: {}",
+        call, account_encrypted, asset_id, amount, shard.encode().to_base58(),
     );
 
     let mut state = if state::exists(&shard) {
@@ -845,32 +1266,40 @@ fn handle_shield_funds_xt(
     let account = AccountId::decode(&mut account_vec.as_slice())
         .sgx_error_with_log("[ShieldFunds] Could not decode account")?;
 
-    if let Err(e) = Stf::execute(
-        &mut state,
-        TrustedCallSigned::new(
-            TrustedCall::balance_shield(account, amount),
-            0,                  //nonce
-            Default::default(), //don't care about signature here
-        ),
-        calls,
-    ) {
-        error!("Error performing Stf::execute. Error: {:?}", e);
+    let shield_call = if asset_id == NATIVE_ASSET_ID {
+        TrustedCall::balance_shield(account.clone(), amount)
+    } else {
+        TrustedCall::asset_shield(account.clone(), asset_id, amount)
+    };
+    // the call is authorized by the on-chain extrinsic, so it does not consume a nonce
+    let call_hash = blake2_256(&xt.encode());
+    debug!("Call hash 0x{}", hex::encode_hex(&call_hash));
+    if let Err(e) = Stf::execute_shield(&mut state, shield_call, call_hash) {
+        error!("Error performing Stf::execute_shield. Error: {:?}", e);
         return Ok(());
     }
 
     let state_hash = state::write(state, &shard)?;
 
-    let xt_call = [SUBSRATEE_REGISTRY_MODULE, CALL_CONFIRMED];
-    let call_hash = blake2_256(&xt.encode());
-    debug!("Call hash 0x{}", hex::encode_hex(&call_hash));
-
-    calls.push(OpaqueCall(
-        (xt_call, shard, call_hash, state_hash.encode()).encode(),
+    calls.push(compose_call_confirmation(
+        shard,
+        call_hash.into(),
+        state_hash,
     ));
 
     Ok(())
 }
 
+/// Composes the on-chain confirmation of a call, naming the hash of the state it resulted in
+fn compose_call_confirmation(
+    shard: ShardIdentifier,
+    call_hash: H256,
+    state_hash: H256,
+) -> OpaqueCall {
+    let xt_call = [SUBSRATEE_REGISTRY_MODULE, CALL_CONFIRMED];
+    OpaqueCall((xt_call, shard, call_hash, state_hash.encode()).encode())
+}
+
 fn decrypt_unchecked_extrinsic(
     xt: UncheckedExtrinsicV4<CallWorkerFn>,
 ) -> SgxResult<(TrustedCallSigned, ShardIdentifier)> {
@@ -907,17 +1336,27 @@ fn handle_trusted_worker_call(
         error!("TrustedCallSigned: bad signature");
         // do not panic here or users will be able to shoot workers dead by supplying a bad signature
         if let Some(author) = author_pointer {
-            // remove call as invalid from pool
-            let inblock = false;
-            author
-                .remove_top(
-                    vec![TrustedOperationOrHash::Operation(
-                        stf_call_signed.into_trusted_operation(true),
-                    )],
-                    shard,
-                    inblock,
-                )
-                .unwrap();
+            reject_operation(
+                &author,
+                stf_call_signed.into_trusted_operation(true),
+                shard,
+                String::from("Invalid signature"),
+                false,
+            );
+        }
+        return Ok(None);
+    }
+
+    if let Some(call_shard) = stf_call_signed.call.shard().filter(|s| **s != shard) {
+        error!("TrustedCall: meant for shard {:?}", call_shard);
+        if let Some(author) = author_pointer {
+            reject_operation(
+                &author,
+                stf_call_signed.into_trusted_operation(true),
+                shard,
+                String::from("Call is meant for another shard"),
+                false,
+            );
         }
         return Ok(None);
     }
@@ -937,25 +1376,29 @@ fn handle_trusted_worker_call(
     Stf::update_storage(state, &update_map);
 
     debug!("execute STF");
-    if let Err(e) = Stf::execute(state, stf_call_signed.clone(), calls) {
-        if let Some(author) = author_pointer {
-            // remove call as invalid from pool
-            let inblock = false;
-            author
-                .remove_top(
-                    vec![TrustedOperationOrHash::Operation(
-                        stf_call_signed.into_trusted_operation(true),
-                    )],
+    let events = match Stf::execute(state, stf_call_signed.clone(), calls) {
+        Ok(events) => events,
+        Err(e) => {
+            error!("Error performing Stf::execute. Error: {:?}", e);
+            if let Some(author) = author_pointer {
+                // a call failing in its dispatch still consumed its nonce
+                let nonce_consumed = Stf::account_nonce(state, stf_call_signed.call.account())
+                    > stf_call_signed.nonce;
+                reject_operation(
+                    &author,
+                    stf_call_signed.into_trusted_operation(true),
                     shard,
-                    inblock,
-                )
-                .unwrap();
-        }
-        error!("Error performing Stf::execute. Error: {:?}", e);
+                    e.to_string(),
+                    nonce_consumed,
+                );
+            }
 
-        return Ok(None);
-    }
+            return Ok(None);
+        }
+    };
 
+    // only direct calls are watched
+    let is_watched = author_pointer.is_some();
     if let Some(author) = author_pointer {
         // TODO: prune instead of remove_top ? Block needs to be known
         // remove call from pool as valid
@@ -977,9 +1420,29 @@ fn handle_trusted_worker_call(
     debug!("Operation hash 0x{}", hex::encode_hex(&operation_hash));
     debug!("Call hash 0x{}", hex::encode_hex(&call_hash));
 
+    // the watching client learns what its call did
+    if is_watched && send_operation_events(operation_hash, shard, events.encode()).is_err() {
+        error!("Could not send the events of the executed operation");
+    }
+
     Ok(Some((H256::from(call_hash), H256::from(operation_hash))))
 }
 
+// tells the watching client why its operation failed and removes the operation from the pool
+//
+// Only an operation that did not consume its nonce takes the operations depending on it along.
+fn reject_operation(
+    author: &Author<&BPool>,
+    operation: TrustedOperation,
+    shard: ShardIdentifier,
+    reason: String,
+    nonce_consumed: bool,
+) {
+    author
+        .reject_top(operation, shard, reason, nonce_consumed)
+        .unwrap();
+}
+
 fn verify_worker_responses(
     responses: Vec<WorkerResponse<Vec<u8>>>,
     header: Header,
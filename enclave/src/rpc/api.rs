@@ -29,7 +29,8 @@ use sp_runtime::{
     generic::BlockId,
     traits::{Block as BlockT, Hash as HashT, Header as HeaderT},
     transaction_validity::{
-        TransactionValidity, TransactionValidityError, UnknownTransaction, ValidTransaction,
+        InvalidTransaction, TransactionValidity, TransactionValidityError, UnknownTransaction,
+        ValidTransaction,
     },
 };
 
@@ -40,6 +41,7 @@ use substratee_stf::{Getter, ShardIdentifier, TrustedOperation as StfTrustedOper
 use substratee_worker_primitives::BlockHash as SidechainBlockHash;
 
 use crate::rpc::error;
+use crate::state;
 
 /// Future that resolves to account nonce.
 pub type Result<T> = core::result::Result<T, ()>;
@@ -82,13 +84,32 @@ where
         &self,
         _source: TrustedOperationSource,
         uxt: StfTrustedOperation,
-        _shard: ShardIdentifier,
+        shard: ShardIdentifier,
     ) -> Self::ValidationFuture {
         let operation = match uxt {
             StfTrustedOperation::direct_call(signed_call) => {
                 let from = signed_call.call.account();
-                let requires = vec![];
-                let provides = vec![from.encode()];
+                let nonce = signed_call.nonce;
+                let account_nonce = match state::account_nonce(&shard, from) {
+                    Ok(account_nonce) => account_nonce,
+                    Err(_) => {
+                        return Box::pin(ready(Ok(Err(TransactionValidityError::Unknown(
+                            UnknownTransaction::CannotLookup,
+                        )))))
+                    }
+                };
+                if nonce < account_nonce {
+                    return Box::pin(ready(Ok(Err(TransactionValidityError::Invalid(
+                        InvalidTransaction::Stale,
+                    )))));
+                }
+                // a call with a future nonce waits in the pool for the call with the preceding nonce
+                let requires = if nonce > account_nonce {
+                    vec![(from, nonce - 1).encode()]
+                } else {
+                    vec![]
+                };
+                let provides = vec![(from, nonce).encode()];
 
                 ValidTransaction {
                     priority: 1 << 20,
@@ -67,6 +67,9 @@ pub enum Error {
     /// Shard does not exist.
     #[display(fmt = "Shard does not exist")]
     InvalidShard,
+    /// Trusted getters are answered directly and not submitted to the pool.
+    #[display(fmt = "Trusted getters can not be submitted to the pool")]
+    GetterSubmitted,
 }
 
 impl std::error::Error for Error {
@@ -129,6 +132,11 @@ impl From<Error> for rpc_core::Error {
 				message: "Shard does not exisit".into(),
 				data: Some(format!("{:?}", e).into()),
 			},
+			Error::GetterSubmitted => rpc_core::Error {
+				code: rpc_core::ErrorCode::ServerError(BAD_FORMAT),
+				message: "Trusted getters can not be submitted to the pool".into(),
+				data: None,
+			},
 			Error::Pool(PoolError::InvalidTrustedOperation) => rpc_core::Error {
 				code: rpc_core::ErrorCode::ServerError(POOL_INVALID_TX),
 				message: "Invalid Trusted Operation".into(),
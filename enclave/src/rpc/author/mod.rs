@@ -18,7 +18,7 @@
 
 //! Substrate block-author/full-node API.
 pub extern crate alloc;
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, string::String, vec::Vec};
 
 use log::*;
 
@@ -81,6 +81,18 @@ pub trait AuthorApi<Hash, BlockHash> {
         inblock: bool,
     ) -> Result<Vec<Hash>>;
 
+    /// Tell the watching client why the execution of the call failed and remove it from the pool.
+    ///
+    /// A call that consumed its nonce is removed with `inblock`, so that the calls
+    /// with the next nonces of its sender stay in the pool.
+    fn reject_top(
+        &self,
+        operation: TrustedOperation,
+        shard: ShardIdentifier,
+        reason: String,
+        inblock: bool,
+    ) -> Result<Vec<Hash>>;
+
     /// Submit an extrinsic to watch.
     ///
     /// See [`TrustedOperationStatus`](sp_transaction_pool::TrustedOperationStatus) for details on transaction
@@ -146,6 +158,9 @@ where
         };
         // decode call
         let stf_operation = match TrustedOperation::decode(&mut request_vec.as_slice()) {
+            Ok(TrustedOperation::get(_)) => {
+                return Box::pin(ready(Err(ClientError::GetterSubmitted.into())))
+            }
             Ok(op) => op,
             Err(_) => return Box::pin(ready(Err(ClientError::BadFormat.into()))),
         };
@@ -232,6 +247,23 @@ where
             .collect())
     }
 
+    fn reject_top(
+        &self,
+        operation: TrustedOperation,
+        shard: ShardIdentifier,
+        reason: String,
+        inblock: bool,
+    ) -> Result<Vec<TxHash<P>>> {
+        let hash = self.pool.hash_of(&operation);
+        // the failure is the final status, the subsequent removal is not reported anymore
+        self.pool.on_execution_failed(&hash, reason);
+        self.remove_top(
+            vec![hash::TrustedOperationOrHash::Hash(hash)],
+            shard,
+            inblock,
+        )
+    }
+
     fn watch_top(&self, ext: Vec<u8>, shard: ShardIdentifier) -> FutureResult<TxHash<P>, RpcError> {
         // check if shard already exists
         if !state::exists(&shard) {
@@ -246,6 +278,9 @@ where
         };
         // decode call
         let stf_operation = match TrustedOperation::decode(&mut request_vec.as_slice()) {
+            Ok(TrustedOperation::get(_)) => {
+                return Box::pin(ready(Err(ClientError::GetterSubmitted.into())))
+            }
             Ok(op) => op,
             Err(_) => return Box::pin(ready(Err(ClientError::BadFormat.into()))),
         };
@@ -175,6 +175,10 @@ where
             .remove_invalid(hashes, shard, inblock)
     }
 
+    fn on_execution_failed(&self, hash: &TxHash<Self>, reason: String) {
+        self.pool.validated_pool().on_execution_failed(hash, reason)
+    }
+
     fn status(&self, shard: ShardIdentifier) -> PoolStatus {
         self.pool.validated_pool().status(shard)
     }
@@ -35,6 +35,7 @@ use std::{
 use sp_core::H256 as Hash;
 
 use codec::{Decode, Encode};
+use lazy_static::lazy_static;
 use log::*;
 
 use crate::rpc::{
@@ -43,41 +44,45 @@ use crate::rpc::{
     basic_pool::BasicPool,
 };
 
-use crate::top_pool::pool::Options as PoolOptions;
+use crate::top_pool::{pool::Options as PoolOptions, primitives::TrustedOperationPool};
 
 use jsonrpc_core::futures::executor;
 use jsonrpc_core::Error as RpcError;
 use jsonrpc_core::*;
 use serde_json::*;
 
-use substratee_stf::ShardIdentifier;
+use substratee_stf::{Getter, ShardIdentifier, Stf, TrustedOperation, STF_VERSION};
 
 use base58::FromBase58;
 use chain_relay::Block;
 
 use substratee_node_primitives::Request;
-use substratee_worker_primitives::RpcReturnValue;
+use substratee_worker_primitives::{request_hash, RpcReturnValue};
+use substratee_worker_primitives::{
+    BlockHash, BlockNumber, GetterResponseKey, SystemHealth, SystemVersion,
+};
 use substratee_worker_primitives::{DirectRequestStatus, TrustedOperationStatus};
 
+use crate::ed25519;
+use crate::io;
 use crate::rsa3072;
+use crate::state;
 use crate::utils::write_slice_and_whitespace_pad;
 
 static GLOBAL_TX_POOL: AtomicPtr<()> = AtomicPtr::new(0 as *mut ());
 
+lazy_static! {
+    // key the return values are signed with, unsealed on first use
+    static ref RETURN_VALUE_SIGNER: SgxMutex<Option<sp_core::ed25519::Pair>> = SgxMutex::new(None);
+}
+
 extern "C" {
     pub fn ocall_update_status_event(
         ret_val: *mut sgx_status_t,
         hash_encoded: *const u8,
         hash_size: u32,
-        status_update_encoded: *const u8,
-        status_size: u32,
-    ) -> sgx_status_t;
-    pub fn ocall_send_status(
-        ret_val: *mut sgx_status_t,
-        hash_encoded: *const u8,
-        hash_size: u32,
-        status_update_encoded: *const u8,
-        status_size: u32,
+        return_value_encoded: *const u8,
+        return_value_size: u32,
     ) -> sgx_status_t;
 }
 
@@ -130,13 +135,187 @@ fn decode_shard_from_base58(shard_base58: String) -> Result<ShardIdentifier, Str
     Ok(shard)
 }
 
-fn compute_encoded_return_error(error_msg: String) -> Vec<u8> {
-    let return_value = RpcReturnValue {
-        value: error_msg.encode(),
-        do_watch: false,
-        status: DirectRequestStatus::Error,
+// the request a return value answers: the hash of the request and the shard it concerns
+type RequestOrigin = (Hash, ShardIdentifier);
+
+// hashes the request of an rpc method, the same way the client does
+fn hash_of_request(method: &str, params: &Params) -> Hash {
+    let encoded_params = params.clone().parse::<Vec<u8>>().unwrap_or_default();
+    request_hash(method, &encoded_params)
+}
+
+fn compute_encoded_return_error(error_msg: String, origin: RequestOrigin) -> Vec<u8> {
+    compute_encoded_return_value(
+        error_msg.encode(),
+        false,
+        DirectRequestStatus::Error,
+        origin,
+    )
+}
+
+// composes the return value signed by the enclave, so that clients can verify its origin
+//
+// the signature covers the request it answers, so it can not be replayed to another request.
+// A value is never sent unsigned, if the key can not be unsealed an error is returned instead.
+fn compute_encoded_return_value(
+    value: Vec<u8>,
+    do_watch: bool,
+    status: DirectRequestStatus,
+    (request_hash, shard): RequestOrigin,
+) -> Vec<u8> {
+    match return_value_signer() {
+        Ok(signer) => RpcReturnValue::new(value, do_watch, status)
+            .for_request(request_hash, shard)
+            .sign(&signer)
+            .encode(),
+        Err(e) => {
+            error!("Could not unseal signing key of the return value: {}", e);
+            RpcReturnValue::new(
+                format!("Could not sign the return value: {}", e).encode(),
+                false,
+                DirectRequestStatus::Error,
+            )
+            .for_request(request_hash, shard)
+            .encode()
+        }
+    }
+}
+
+fn return_value_signer() -> SgxResult<sp_core::ed25519::Pair> {
+    let mut signer = RETURN_VALUE_SIGNER.lock().unwrap();
+    if signer.is_none() {
+        *signer = Some(ed25519::unseal_pair()?);
+    }
+    Ok(signer.clone().unwrap())
+}
+
+// runs the getter of an encrypted trusted operation against the state of the requested shard
+//
+// returns the encoded result, encrypted with the response key of the client if supplied
+fn get_state(request: Request) -> Result<Vec<u8>, String> {
+    match decrypt_trusted_operation(&request)? {
+        (TrustedOperation::get(getter), response_key) => {
+            let value = execute_getter(&request.shard, getter)?;
+            Ok(encrypt_getter_result(value, response_key))
+        }
+        _ => Err("Trusted operation is not a getter".to_owned()),
+    }
+}
+
+// the shielded trusted operation may be followed by the key the client wants the result of
+// its getter to be encrypted with
+fn decrypt_trusted_operation(
+    request: &Request,
+) -> Result<(TrustedOperation, Option<GetterResponseKey>), String> {
+    let rsa_keypair =
+        rsa3072::unseal_pair().map_err(|e| format!("Could not unseal rsa key: {}", e))?;
+    let operation_vec = rsa3072::decrypt(&request.cyphertext, &rsa_keypair)
+        .map_err(|_| "Could not decrypt trusted operation".to_owned())?;
+    let mut operation_slice = operation_vec.as_slice();
+    let operation = TrustedOperation::decode(&mut operation_slice)
+        .map_err(|_| "Could not decode trusted operation".to_owned())?;
+    if operation_slice.is_empty() {
+        return Ok((operation, None));
+    }
+    let response_key = GetterResponseKey::decode(&mut operation_slice)
+        .map_err(|_| "Could not decode getter response key".to_owned())?;
+    Ok((operation, Some(response_key)))
+}
+
+fn encrypt_getter_result(
+    value: Option<Vec<u8>>,
+    response_key: Option<GetterResponseKey>,
+) -> Vec<u8> {
+    let mut encoded = value.encode();
+    if let Some(key) = response_key {
+        key.de_or_encrypt(&mut encoded);
+    }
+    encoded
+}
+
+// answers the getter from the current state of the shard
+fn execute_getter(shard: &ShardIdentifier, getter: Getter) -> Result<Option<Vec<u8>>, String> {
+    if !state::exists(shard) {
+        return Err(format!("Shard {:?} does not exist", shard));
+    }
+    if let Getter::trusted(ref trusted_getter_signed) = getter {
+        if !trusted_getter_signed.verify_signature() {
+            return Err("Invalid signature of trusted getter".to_owned());
+        }
+    }
+    let mut state = state::load(shard).map_err(|e| format!("Could not load shard state: {}", e))?;
+    Ok(Stf::get_state(&mut state, getter))
+}
+
+// returns the encoded return value if the request is a getter, which is not submitted to the pool
+fn answer_if_getter(request: &Request, origin: RequestOrigin) -> Option<Vec<u8>> {
+    match decrypt_trusted_operation(request) {
+        Ok((TrustedOperation::get(getter), response_key)) => {
+            Some(match execute_getter(&request.shard, getter) {
+                Ok(value) => compute_encoded_return_value(
+                    encrypt_getter_result(value, response_key),
+                    false,
+                    DirectRequestStatus::Ok,
+                    origin,
+                ),
+                Err(error_msg) => compute_encoded_return_error(error_msg, origin),
+            })
+        }
+        _ => None,
+    }
+}
+
+// returns number and hash of the latest sidechain block of the shard, if any
+fn get_sidechain_head(shard: &ShardIdentifier) -> Result<Option<(BlockNumber, BlockHash)>, String> {
+    if !state::exists(shard) {
+        return Err(format!("Shard {:?} does not exist", shard));
+    }
+    let mut state = state::load(shard).map_err(|e| format!("Could not load shard state: {}", e))?;
+    match (
+        Stf::get_sidechain_block_number(&mut state),
+        Stf::get_last_block_hash(&mut state),
+    ) {
+        (Some(number), Some(hash)) => Ok(Some((number, hash))),
+        _ => Ok(None),
+    }
+}
+
+fn get_system_health(shard: &ShardIdentifier) -> Result<SystemHealth, String> {
+    if !state::exists(shard) {
+        return Err(format!("Shard {:?} does not exist", shard));
+    }
+    let mut state = state::load(shard).map_err(|e| format!("Could not load shard state: {}", e))?;
+
+    let pool_status = {
+        let tx_pool_mutex =
+            load_top_pool().ok_or_else(|| "Top pool is not initialized".to_owned())?;
+        let tx_pool_guard = tx_pool_mutex.lock().unwrap();
+        tx_pool_guard.status(*shard)
+    };
+
+    // the chain relay is not necessarily initialized yet
+    let (relay_finalized_block_number, stuck_extrinsics) = match io::light_validation::unseal() {
+        Ok(validator) => {
+            let relay_id = crate::layer_one_relay_id();
+            (
+                validator
+                    .latest_finalized_header(relay_id)
+                    .ok()
+                    .map(|header| header.number),
+                validator.num_stuck_extrinsics(relay_id).ok(),
+            )
+        }
+        Err(_) => (None, None),
     };
-    return_value.encode()
+
+    Ok(SystemHealth {
+        sidechain_block_number: Stf::get_sidechain_block_number(&mut state),
+        pool_ready: pool_status.ready as u64,
+        pool_future: pool_status.future as u64,
+        relay_finalized_block_number,
+        state_layer_one_block_number: Stf::get_layer_one_block_number(&mut state),
+        stuck_extrinsics,
+    })
 }
 
 fn init_io_handler() -> IoHandler {
@@ -150,16 +329,22 @@ fn init_io_handler() -> IoHandler {
     io.add_sync_method(
         author_submit_and_watch_extrinsic_name,
         move |params: Params| {
+            let request_hash = hash_of_request(author_submit_and_watch_extrinsic_name, &params);
             match params.parse::<Vec<u8>>() {
                 Ok(encoded_params) => {
-                    // Aquire lock
-                    let tx_pool_mutex = load_top_pool().unwrap();
-                    let tx_pool_guard = tx_pool_mutex.lock().unwrap();
-                    let tx_pool = Arc::new(tx_pool_guard.deref());
-                    let author = Author::new(tx_pool);
-
                     match Request::decode(&mut encoded_params.as_slice()) {
                         Ok(request) => {
+                            let origin = (request_hash, request.shard);
+                            // trusted getters are answered right away
+                            if let Some(json_value) = answer_if_getter(&request, origin) {
+                                return Ok(json!(json_value));
+                            }
+                            // Aquire lock
+                            let tx_pool_mutex = load_top_pool().unwrap();
+                            let tx_pool_guard = tx_pool_mutex.lock().unwrap();
+                            let tx_pool = Arc::new(tx_pool_guard.deref());
+                            let author = Author::new(tx_pool);
+
                             let shard: ShardIdentifier = request.shard;
                             let encrypted_trusted_call: Vec<u8> = request.cyphertext;
                             let result = async {
@@ -169,26 +354,32 @@ fn init_io_handler() -> IoHandler {
                             };
                             let response: Result<Hash, RpcError> = executor::block_on(result);
                             let json_value = match response {
-                                Ok(hash_value) => RpcReturnValue {
-                                    do_watch: true,
-                                    value: hash_value.encode(),
-                                    status: DirectRequestStatus::TrustedOperationStatus(
+                                Ok(hash_value) => compute_encoded_return_value(
+                                    hash_value.encode(),
+                                    true,
+                                    DirectRequestStatus::TrustedOperationStatus(
                                         TrustedOperationStatus::Submitted,
                                     ),
+                                    origin,
+                                ),
+                                Err(rpc_error) => {
+                                    compute_encoded_return_error(rpc_error.message, origin)
                                 }
-                                .encode(),
-                                Err(rpc_error) => compute_encoded_return_error(rpc_error.message),
                             };
                             Ok(json!(json_value))
                         }
                         Err(_) => Ok(json!(compute_encoded_return_error(
-                            "Could not decode request".to_owned()
+                            "Could not decode request".to_owned(),
+                            (request_hash, Default::default())
                         ))),
                     }
                 }
                 Err(e) => {
                     let error_msg: String = format!("Could not submit trusted call due to: {}", e);
-                    Ok(json!(compute_encoded_return_error(error_msg)))
+                    Ok(json!(compute_encoded_return_error(
+                        error_msg,
+                        (request_hash, Default::default())
+                    )))
                 }
             }
         },
@@ -198,42 +389,54 @@ fn init_io_handler() -> IoHandler {
     let author_submit_extrinsic_name: &str = "author_submitExtrinsic";
     rpc_methods_vec.push(author_submit_extrinsic_name);
     io.add_sync_method(author_submit_extrinsic_name, move |params: Params| {
+        let request_hash = hash_of_request(author_submit_extrinsic_name, &params);
         match params.parse::<Vec<u8>>() {
             Ok(encoded_params) => {
-                // Aquire lock
-                let tx_pool_mutex = load_top_pool().unwrap();
-                let tx_pool_guard = tx_pool_mutex.lock().unwrap();
-                let tx_pool = Arc::new(tx_pool_guard.deref());
-                let author = Author::new(tx_pool);
-
                 match Request::decode(&mut encoded_params.as_slice()) {
                     Ok(request) => {
+                        let origin = (request_hash, request.shard);
+                        // trusted getters are answered right away
+                        if let Some(json_value) = answer_if_getter(&request, origin) {
+                            return Ok(json!(json_value));
+                        }
+                        // Aquire lock
+                        let tx_pool_mutex = load_top_pool().unwrap();
+                        let tx_pool_guard = tx_pool_mutex.lock().unwrap();
+                        let tx_pool = Arc::new(tx_pool_guard.deref());
+                        let author = Author::new(tx_pool);
+
                         let shard: ShardIdentifier = request.shard;
                         let encrypted_trusted_op: Vec<u8> = request.cyphertext;
                         let result =
                             async { author.submit_top(encrypted_trusted_op.clone(), shard).await };
                         let response: Result<Hash, RpcError> = executor::block_on(result);
                         let json_value = match response {
-                            Ok(hash_value) => RpcReturnValue {
-                                do_watch: false,
-                                value: hash_value.encode(),
-                                status: DirectRequestStatus::TrustedOperationStatus(
+                            Ok(hash_value) => compute_encoded_return_value(
+                                hash_value.encode(),
+                                false,
+                                DirectRequestStatus::TrustedOperationStatus(
                                     TrustedOperationStatus::Submitted,
                                 ),
+                                origin,
+                            ),
+                            Err(rpc_error) => {
+                                compute_encoded_return_error(rpc_error.message, origin)
                             }
-                            .encode(),
-                            Err(rpc_error) => compute_encoded_return_error(rpc_error.message),
                         };
                         Ok(json!(json_value))
                     }
                     Err(_) => Ok(json!(compute_encoded_return_error(
-                        "Could not decode request".to_owned()
+                        "Could not decode request".to_owned(),
+                        (request_hash, Default::default())
                     ))),
                 }
             }
             Err(e) => {
                 let error_msg: String = format!("Could not submit trusted call due to: {}", e);
-                Ok(json!(compute_encoded_return_error(error_msg)))
+                Ok(json!(compute_encoded_return_error(
+                    error_msg,
+                    (request_hash, Default::default())
+                )))
             }
         }
     });
@@ -242,6 +445,12 @@ fn init_io_handler() -> IoHandler {
     let author_pending_extrinsic_name: &str = "author_pendingExtrinsics";
     rpc_methods_vec.push(author_pending_extrinsic_name);
     io.add_sync_method(author_pending_extrinsic_name, move |params: Params| {
+        // the shards are passed as strings, so the request is hashed over their encoding
+        let hash = match params.clone().parse::<Vec<String>>() {
+            Ok(shards) => request_hash(author_pending_extrinsic_name, &shards.encode()),
+            Err(_) => Default::default(),
+        };
+        let origin = (hash, Default::default());
         match params.parse::<Vec<String>>() {
             Ok(shards) => {
                 // Aquire tx_pool lock
@@ -260,16 +469,17 @@ fn init_io_handler() -> IoHandler {
                         retrieved_operations.push(vec_of_operations);
                     }
                 }
-                let json_value = RpcReturnValue {
-                    do_watch: false,
-                    value: retrieved_operations.encode(),
-                    status: DirectRequestStatus::Ok,
-                };
-                Ok(json!(json_value.encode()))
+                let json_value = compute_encoded_return_value(
+                    retrieved_operations.encode(),
+                    false,
+                    DirectRequestStatus::Ok,
+                    origin,
+                );
+                Ok(json!(json_value))
             }
             Err(e) => {
                 let error_msg: String = format!("Could not retrieve pending calls due to: {}", e);
-                Ok(json!(compute_encoded_return_error(error_msg)))
+                Ok(json!(compute_encoded_return_error(error_msg, origin)))
             }
         }
     });
@@ -277,12 +487,16 @@ fn init_io_handler() -> IoHandler {
     // author_getShieldingKey
     let rsa_pubkey_name: &str = "author_getShieldingKey";
     rpc_methods_vec.push(rsa_pubkey_name);
-    io.add_sync_method(rsa_pubkey_name, move |_: Params| {
+    io.add_sync_method(rsa_pubkey_name, move |params: Params| {
+        let origin = (
+            hash_of_request(rsa_pubkey_name, &params),
+            Default::default(),
+        );
         let rsa_pubkey = match rsa3072::unseal_pubkey() {
             Ok(key) => key,
             Err(status) => {
                 let error_msg: String = format!("Could not get rsa pubkey due to: {}", status);
-                return Ok(json!(compute_encoded_return_error(error_msg)));
+                return Ok(json!(compute_encoded_return_error(error_msg, origin)));
             }
         };
 
@@ -293,68 +507,193 @@ fn init_io_handler() -> IoHandler {
                     "[Enclave] can't serialize rsa_pubkey {:?} {}",
                     rsa_pubkey, x
                 );
-                return Ok(json!(compute_encoded_return_error(error_msg)));
+                return Ok(json!(compute_encoded_return_error(error_msg, origin)));
             }
         };
-        let json_value =
-            RpcReturnValue::new(rsa_pubkey_json.encode(), false, DirectRequestStatus::Ok);
-        Ok(json!(json_value.encode()))
+        let json_value = compute_encoded_return_value(
+            rsa_pubkey_json.encode(),
+            false,
+            DirectRequestStatus::Ok,
+            origin,
+        );
+        Ok(json!(json_value))
     });
 
     // chain_subscribeAllHeads
+    // returns the current head, the untrusted server pushes the headers of new blocks
     let chain_subscribe_all_heads_name: &str = "chain_subscribeAllHeads";
     rpc_methods_vec.push(chain_subscribe_all_heads_name);
-    io.add_sync_method(chain_subscribe_all_heads_name, |_: Params| {
-        let parsed = "world";
-        Ok(Value::String(format!("hello, {}", parsed)))
+    io.add_sync_method(chain_subscribe_all_heads_name, move |params: Params| {
+        let request_hash = hash_of_request(chain_subscribe_all_heads_name, &params);
+        match params.parse::<Vec<u8>>() {
+            Ok(encoded_params) => {
+                let json_value = match ShardIdentifier::decode(&mut encoded_params.as_slice()) {
+                    Ok(shard) => match get_sidechain_head(&shard) {
+                        Ok(head) => compute_encoded_return_value(
+                            head.encode(),
+                            true,
+                            DirectRequestStatus::Ok,
+                            (request_hash, shard),
+                        ),
+                        Err(error_msg) => {
+                            compute_encoded_return_error(error_msg, (request_hash, shard))
+                        }
+                    },
+                    Err(_) => compute_encoded_return_error(
+                        "Could not decode shard".to_owned(),
+                        (request_hash, Default::default()),
+                    ),
+                };
+                Ok(json!(json_value))
+            }
+            Err(e) => {
+                let error_msg: String = format!("Could not get sidechain head due to: {}", e);
+                Ok(json!(compute_encoded_return_error(
+                    error_msg,
+                    (request_hash, Default::default())
+                )))
+            }
+        }
     });
 
     // state_getMetadata
     let state_get_metadata_name: &str = "state_getMetadata";
     rpc_methods_vec.push(state_get_metadata_name);
-    io.add_sync_method(state_get_metadata_name, |_: Params| {
-        let parsed = "world";
-        Ok(Value::String(format!("hello, {}", parsed)))
+    io.add_sync_method(state_get_metadata_name, move |params: Params| {
+        let json_value = compute_encoded_return_value(
+            Stf::get_runtime_metadata(),
+            false,
+            DirectRequestStatus::Ok,
+            (
+                hash_of_request(state_get_metadata_name, &params),
+                Default::default(),
+            ),
+        );
+        Ok(json!(json_value))
     });
 
     // state_getRuntimeVersion
     let state_get_runtime_version_name: &str = "state_getRuntimeVersion";
     rpc_methods_vec.push(state_get_runtime_version_name);
-    io.add_sync_method(state_get_runtime_version_name, |_: Params| {
-        let parsed = "world";
-        Ok(Value::String(format!("hello, {}", parsed)))
+    io.add_sync_method(state_get_runtime_version_name, move |params: Params| {
+        let json_value = compute_encoded_return_value(
+            Stf::get_runtime_version(),
+            false,
+            DirectRequestStatus::Ok,
+            (
+                hash_of_request(state_get_runtime_version_name, &params),
+                Default::default(),
+            ),
+        );
+        Ok(json!(json_value))
     });
 
     // state_get
     let state_get_name: &str = "state_get";
     rpc_methods_vec.push(state_get_name);
-    io.add_sync_method(state_get_name, |_: Params| {
-        let parsed = "world";
-        Ok(Value::String(format!("hello, {}", parsed)))
+    io.add_sync_method(state_get_name, move |params: Params| {
+        let request_hash = hash_of_request(state_get_name, &params);
+        match params.parse::<Vec<u8>>() {
+            Ok(encoded_params) => match Request::decode(&mut encoded_params.as_slice()) {
+                Ok(request) => {
+                    let origin = (request_hash, request.shard);
+                    let json_value = match get_state(request) {
+                        Ok(value) => compute_encoded_return_value(
+                            value,
+                            false,
+                            DirectRequestStatus::Ok,
+                            origin,
+                        ),
+                        Err(error_msg) => compute_encoded_return_error(error_msg, origin),
+                    };
+                    Ok(json!(json_value))
+                }
+                Err(_) => Ok(json!(compute_encoded_return_error(
+                    "Could not decode request".to_owned(),
+                    (request_hash, Default::default())
+                ))),
+            },
+            Err(e) => {
+                let error_msg: String = format!("Could not get state due to: {}", e);
+                Ok(json!(compute_encoded_return_error(
+                    error_msg,
+                    (request_hash, Default::default())
+                )))
+            }
+        }
     });
 
     // system_health
     let state_health_name: &str = "system_health";
     rpc_methods_vec.push(state_health_name);
-    io.add_sync_method(state_health_name, |_: Params| {
-        let parsed = "world";
-        Ok(Value::String(format!("hello, {}", parsed)))
+    io.add_sync_method(state_health_name, move |params: Params| {
+        let request_hash = hash_of_request(state_health_name, &params);
+        match params.parse::<Vec<u8>>() {
+            Ok(encoded_params) => {
+                let json_value = match ShardIdentifier::decode(&mut encoded_params.as_slice()) {
+                    Ok(shard) => match get_system_health(&shard) {
+                        Ok(health) => compute_encoded_return_value(
+                            health.encode(),
+                            false,
+                            DirectRequestStatus::Ok,
+                            (request_hash, shard),
+                        ),
+                        Err(error_msg) => {
+                            compute_encoded_return_error(error_msg, (request_hash, shard))
+                        }
+                    },
+                    Err(_) => compute_encoded_return_error(
+                        "Could not decode shard".to_owned(),
+                        (request_hash, Default::default()),
+                    ),
+                };
+                Ok(json!(json_value))
+            }
+            Err(e) => {
+                let error_msg: String = format!("Could not get system health due to: {}", e);
+                Ok(json!(compute_encoded_return_error(
+                    error_msg,
+                    (request_hash, Default::default())
+                )))
+            }
+        }
     });
 
     // system_name
     let state_name_name: &str = "system_name";
     rpc_methods_vec.push(state_name_name);
-    io.add_sync_method(state_name_name, |_: Params| {
-        let parsed = "world";
-        Ok(Value::String(format!("hello, {}", parsed)))
+    io.add_sync_method(state_name_name, move |params: Params| {
+        let name: String = env!("CARGO_PKG_NAME").to_owned();
+        let json_value = compute_encoded_return_value(
+            name.encode(),
+            false,
+            DirectRequestStatus::Ok,
+            (
+                hash_of_request(state_name_name, &params),
+                Default::default(),
+            ),
+        );
+        Ok(json!(json_value))
     });
 
     // system_version
     let state_version_name: &str = "system_version";
     rpc_methods_vec.push(state_version_name);
-    io.add_sync_method(state_version_name, |_: Params| {
-        let parsed = "world";
-        Ok(Value::String(format!("hello, {}", parsed)))
+    io.add_sync_method(state_version_name, move |params: Params| {
+        let version = SystemVersion {
+            enclave: env!("CARGO_PKG_VERSION").to_owned(),
+            stf: STF_VERSION.to_owned(),
+        };
+        let json_value = compute_encoded_return_value(
+            version.encode(),
+            false,
+            DirectRequestStatus::Ok,
+            (
+                hash_of_request(state_version_name, &params),
+                Default::default(),
+            ),
+        );
+        Ok(json!(json_value))
     });
 
     // returns all rpcs methods
@@ -395,47 +734,61 @@ pub unsafe extern "C" fn call_rpc_methods(
 
 pub fn update_status_event<H: Encode>(
     hash: H,
+    shard: ShardIdentifier,
     status_update: TrustedOperationStatus,
 ) -> Result<(), ()> {
-    let mut rt: sgx_status_t = sgx_status_t::SGX_ERROR_UNEXPECTED;
-
     let hash_encoded = hash.encode();
-    let status_update_encoded = status_update.encode();
-
-    let res = unsafe {
-        ocall_update_status_event(
-            &mut rt as *mut sgx_status_t,
-            hash_encoded.as_ptr(),
-            hash_encoded.len() as u32,
-            status_update_encoded.as_ptr(),
-            status_update_encoded.len() as u32,
-        )
-    };
-
-    if rt != sgx_status_t::SGX_SUCCESS {
-        return Err(());
-    }
-
-    if res != sgx_status_t::SGX_SUCCESS {
-        return Err(());
-    }
+    send_status_update(hash_encoded.clone(), shard, hash_encoded, status_update)
+}
 
-    Ok(())
+/// Pushes the encoded events of an executed operation to the watching client
+pub fn send_operation_events<H: Encode>(
+    hash: H,
+    shard: ShardIdentifier,
+    events_encoded: Vec<u8>,
+) -> Result<(), ()> {
+    send_status_update(
+        hash.encode(),
+        shard,
+        events_encoded,
+        TrustedOperationStatus::Executed,
+    )
 }
 
-pub fn send_state<H: Encode>(hash: H, value_opt: Option<Vec<u8>>) -> Result<(), ()> {
+// the status updates of an operation answer the request which submitted it,
+// so they are bound to the hash of the operation
+fn send_status_update(
+    hash_encoded: Vec<u8>,
+    shard: ShardIdentifier,
+    value: Vec<u8>,
+    status_update: TrustedOperationStatus,
+) -> Result<(), ()> {
     let mut rt: sgx_status_t = sgx_status_t::SGX_ERROR_UNEXPECTED;
-
-    let hash_encoded = hash.encode();
-    let value_encoded = value_opt.encode();
+    let operation_hash = Hash::decode(&mut hash_encoded.as_slice()).map_err(|_| ())?;
+
+    // watching stops once the operation has reached a final state
+    let do_watch = !matches!(
+        status_update,
+        TrustedOperationStatus::Invalid
+            | TrustedOperationStatus::ExecutionFailed(_)
+            | TrustedOperationStatus::InSidechainBlock(_)
+            | TrustedOperationStatus::Finalized
+            | TrustedOperationStatus::Usurped
+    );
+    let return_value_encoded = compute_encoded_return_value(
+        value,
+        do_watch,
+        DirectRequestStatus::TrustedOperationStatus(status_update),
+        (operation_hash, shard),
+    );
 
     let res = unsafe {
-        ocall_send_status(
+        ocall_update_status_event(
             &mut rt as *mut sgx_status_t,
             hash_encoded.as_ptr(),
             hash_encoded.len() as u32,
-            value_encoded.as_ptr(),
-            value_encoded.len() as u32,
+            return_value_encoded.as_ptr(),
+            return_value_encoded.len() as u32,
         )
     };
 
@@ -15,9 +15,12 @@
 
 */
 
+use std::collections::hash_map::{Entry, HashMap};
 use std::fs;
 
 use std::io::Write;
+use std::string::String;
+use std::sync::SgxMutex;
 use std::vec::Vec;
 
 use log::*;
@@ -25,20 +28,27 @@ use sgx_tcrypto::rsgx_sha256_slice;
 use sgx_types::*;
 
 use crate::aes;
-use crate::constants::{ENCRYPTED_STATE_FILE, SHARDS_PATH};
+use crate::constants::{ENCRYPTED_STATE_FILE, SEALED_GENESIS_CONFIG_FILE, SHARDS_PATH};
 use crate::hex;
 use crate::io;
 use crate::utils::UnwrapOrSgxErrorUnexpected;
 use base58::{FromBase58, ToBase58};
 use codec::{Decode, Encode};
+use lazy_static::lazy_static;
 use sgx_externalities::SgxExternalitiesTypeTrait;
 use sp_core::H256;
 use std::path::Path;
 use substratee_stf::{
-    ShardIdentifier, State as StfState, StateType as StfStateType,
+    AccountId, GenesisConfig, Index, ShardIdentifier, State as StfState, StateType as StfStateType,
     StateTypeDiff as StfStateTypeDiff, Stf,
 };
 
+lazy_static! {
+    // states the account nonces are read from, dropped whenever their shard is written
+    static ref NONCE_STATES: SgxMutex<HashMap<ShardIdentifier, StfState>> =
+        SgxMutex::new(HashMap::new());
+}
+
 pub fn load(shard: &ShardIdentifier) -> SgxResult<StfState> {
     // load last state
     let state_path = format!(
@@ -54,7 +64,7 @@ pub fn load(shard: &ShardIdentifier) -> SgxResult<StfState> {
     let state: StfStateType = match state_vec.len() {
         0 => {
             debug!("state at {} is empty. will initialize it.", state_path);
-            Stf::init_state().state
+            Stf::init_state_from_genesis(&load_genesis_config(shard)?).state
         }
         n => {
             debug!(
@@ -99,9 +109,22 @@ pub fn write(state: StfState, shard: &ShardIdentifier) -> SgxResult<H256> {
     );
 
     io::write(&cyphertext, &state_path)?;
+    NONCE_STATES.lock().sgx_error()?.remove(shard);
     Ok(state_hash.into())
 }
 
+/// Returns the nonce of the account in the current state of the shard
+///
+/// The state is only loaded and decrypted once after every write of the shard.
+pub fn account_nonce(shard: &ShardIdentifier, account: &AccountId) -> SgxResult<Index> {
+    let mut states = NONCE_STATES.lock().sgx_error()?;
+    let state = match states.entry(*shard) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => entry.insert(load(shard)?),
+    };
+    Ok(Stf::account_nonce(state, account))
+}
+
 pub fn exists(shard: &ShardIdentifier) -> bool {
     Path::new(&format!(
         "{}/{}/{}",
@@ -130,6 +153,34 @@ pub fn init_shard(shard: &ShardIdentifier) -> SgxResult<()> {
     file.write_all(b"").sgx_error()
 }
 
+/// Initializes the shard and seals its genesis config, from which the state is built on first load
+pub fn init_shard_with_genesis(
+    shard: &ShardIdentifier,
+    genesis_config: &GenesisConfig,
+) -> SgxResult<()> {
+    init_shard(shard)?;
+    io::seal(&genesis_config.encode(), &genesis_config_path(shard))?;
+    Ok(())
+}
+
+/// Returns the sealed genesis config of the shard, or the default one if it has none
+pub fn load_genesis_config(shard: &ShardIdentifier) -> SgxResult<GenesisConfig> {
+    let path = genesis_config_path(shard);
+    if !Path::new(&path).exists() {
+        return Ok(GenesisConfig::default());
+    }
+    GenesisConfig::decode(&mut io::unseal(&path)?.as_slice()).sgx_error()
+}
+
+fn genesis_config_path(shard: &ShardIdentifier) -> String {
+    format!(
+        "{}/{}/{}",
+        SHARDS_PATH,
+        shard.encode().to_base58(),
+        SEALED_GENESIS_CONFIG_FILE
+    )
+}
+
 fn read(path: &str) -> SgxResult<Vec<u8>> {
     let mut bytes = match io::read(path) {
         Ok(vec) => match vec.len() {
@@ -225,6 +276,7 @@ pub fn test_encrypt_decrypt_state_type_works() {
 }
 
 use crate::tests::ensure_no_empty_shard_directory_exists;
+use substratee_stf::{TrustedCall, TrustedCallSigned};
 
 pub fn test_write_and_load_state_works() {
     // given
@@ -250,6 +302,38 @@ pub fn test_write_and_load_state_works() {
     remove_shard_dir(&shard);
 }
 
+pub fn test_account_nonce_follows_the_written_state() {
+    // given
+    ensure_no_empty_shard_directory_exists();
+
+    let shard: ShardIdentifier = [95u8; 32].into();
+    let account = AccountId::new([1u8; 32]);
+    let mut state = Stf::init_state();
+    if !exists(&shard) {
+        init_shard(&shard).unwrap();
+    }
+    write(state.clone(), &shard).unwrap();
+    assert_eq!(account_nonce(&shard, &account).unwrap(), 0);
+
+    // when
+    let shield = TrustedCall::balance_shield(account.clone(), 10);
+    Stf::execute_shield(&mut state, shield, [1u8; 32]).unwrap();
+    let transfer = TrustedCall::balance_transfer(account.clone(), AccountId::new([2u8; 32]), 5);
+    Stf::execute(
+        &mut state,
+        TrustedCallSigned::new(transfer, 0, Default::default()),
+        &mut Vec::new(),
+    )
+    .unwrap();
+    write(state, &shard).unwrap();
+
+    // then
+    assert_eq!(account_nonce(&shard, &account).unwrap(), 1);
+
+    // clean up
+    remove_shard_dir(&shard);
+}
+
 pub fn remove_shard_dir(shard: &ShardIdentifier) {
     std::fs::remove_dir_all(&format!("{}/{}", SHARDS_PATH, shard.encode().to_base58())).unwrap();
 }
\ No newline at end of file
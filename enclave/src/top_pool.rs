@@ -2,7 +2,6 @@ mod future;
 mod listener;
 
 mod validated_pool;
-mod watcher;
 
 pub mod base_pool;
 pub mod error;
@@ -11,3 +10,4 @@ pub mod primitives;
 pub mod ready; // pub necessary for unit tests
 pub mod rotator; // pub necessary for unit tests
 pub mod tracked_map; // pub necessary for unit tests
+pub mod watcher; // pub necessary for unit tests
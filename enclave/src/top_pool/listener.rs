@@ -24,7 +24,7 @@ use std::{collections::HashMap, fmt::Debug, hash, string::String, vec::Vec};
 use crate::top_pool::watcher::Watcher;
 use codec::Encode;
 
-use substratee_worker_primitives::BlockHash as SidechainBlockHash;
+use substratee_worker_primitives::{BlockHash as SidechainBlockHash, ShardIdentifier};
 
 /// Extrinsic pool default listener.
 pub struct Listener<H: hash::Hash + Eq> {
@@ -65,8 +65,8 @@ impl<H: hash::Hash + traits::Member + Encode> Listener<H> {
     /// Creates a new watcher for given verified extrinsic.
     ///
     /// The watcher can be used to subscribe to life-cycle events of that extrinsic.
-    pub fn create_watcher(&mut self, hash: H) {
-        let new_watcher = Watcher::new_watcher(hash.clone());
+    pub fn create_watcher(&mut self, hash: H, shard: ShardIdentifier) {
+        let new_watcher = Watcher::new_watcher(hash.clone(), shard);
         self.watchers.insert(hash, new_watcher);
         //let sender = self.watchers.entry(hash.clone()).or_insert_with(Watcher::default);
         //sender.new_watcher(hash)
@@ -107,6 +107,17 @@ impl<H: hash::Hash + traits::Member + Encode> Listener<H> {
         self.fire(tx, |watcher| watcher.invalid());
     }
 
+    /// TrustedOperation was rejected by the STF.
+    pub fn execution_failed(&mut self, tx: &H, reason: String) {
+        debug!(target: "txpool", "[{:?}] Execution failed: {}", tx, reason);
+        self.fire(tx, |watcher| watcher.execution_failed(reason));
+    }
+
+    /// Returns true if the status of the operation is still reported to a client.
+    pub fn is_watched(&self, tx: &H) -> bool {
+        self.watchers.contains_key(tx)
+    }
+
     /// TrustedOperation was pruned from the pool.
     pub fn pruned(&mut self, block_hash: SidechainBlockHash, tx: &H) {
         debug!(target: "txpool", "[{:?}] Pruned at {:?}", tx, block_hash);
@@ -235,6 +235,9 @@ pub trait TrustedOperationPool: Send + Sync {
         inblock: bool,
     ) -> Vec<Arc<Self::InPoolOperation>>;
 
+    /// Notify the watcher of an operation that its execution failed, for the given reason.
+    fn on_execution_failed(&self, hash: &TxHash<Self>, reason: String);
+
     // *** logging
     /// Returns pool status.
     fn status(&self, shard: ShardIdentifier) -> PoolStatus;
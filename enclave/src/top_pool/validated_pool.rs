@@ -280,7 +280,7 @@ where
                     .expect("One extrinsic passed; one result returned; qed");
                 // TODO: How to return / notice if Future or Ready queue?
                 if let Ok(hash) = hash_result {
-                    self.listener.write().unwrap().create_watcher(hash);
+                    self.listener.write().unwrap().create_watcher(hash, shard);
                 }
                 hash_result
             }
@@ -615,6 +615,8 @@ where
     /// to prevent them from entering the pool right away.
     /// Note this is not the case for the dependent operations - those may
     /// still be valid so we want to be able to re-import them.
+    /// Operations which were included in a block (`inblock`) are pruned instead,
+    /// which keeps and possibly promotes their dependent operations.
     pub fn remove_invalid(
         &self,
         hashes: &[ExtrinsicHash<B>],
@@ -631,19 +633,27 @@ where
         // temporarily ban invalid operations
         self.rotator.ban(&Instant::now(), hashes.iter().cloned());
 
+        if inblock {
+            // executed operations are pruned by their tags, so that the operations
+            // depending on them (e.g. with the next nonce) stay in the pool
+            let tags = self
+                .extrinsics_tags(hashes, shard)
+                .into_iter()
+                .flatten()
+                .flatten();
+            return match self.prune_tags(tags, shard) {
+                Ok(status) => status.pruned,
+                Err(_) => vec![],
+            };
+        }
+
         let invalid = self.pool.write().unwrap().remove_subtree(hashes, shard);
 
         log::debug!(target: "txpool", "Removed invalid operations: {:?}", invalid);
 
         let mut listener = self.listener.write().unwrap();
-        if inblock {
-            for _tx in &invalid {
-                //listener.in_block(&tx.hash);
-            }
-        } else {
-            for tx in &invalid {
-                listener.invalid(&tx.hash);
-            }
+        for tx in &invalid {
+            listener.invalid(&tx.hash);
         }
 
         invalid
@@ -680,6 +690,19 @@ where
         Ok(())
     }
 
+    /// Notify the listener that the execution of an operation failed
+    pub fn on_execution_failed(&self, hash: &ExtrinsicHash<B>, reason: String) {
+        self.listener
+            .write()
+            .unwrap()
+            .execution_failed(hash, reason)
+    }
+
+    /// Returns true if the status of the operation is reported to a watching client
+    pub fn is_watched(&self, hash: &ExtrinsicHash<B>) -> bool {
+        self.listener.read().unwrap().is_watched(hash)
+    }
+
     /// Notify the listener of retracted blocks
     pub fn on_block_retracted(&self, block_hash: SidechainBlockHash) {
         self.listener.write().unwrap().retracted(block_hash)
@@ -28,7 +28,9 @@ use std::hash;
 
 //use crate::top_pool::primitives::TrustedOperationStatus
 use crate::rpc::worker_api_direct;
-use substratee_worker_primitives::{BlockHash as SidechainBlockHash, TrustedOperationStatus};
+use substratee_worker_primitives::{
+    BlockHash as SidechainBlockHash, ShardIdentifier, TrustedOperationStatus,
+};
 
 /// Extrinsic watcher.
 ///
@@ -37,6 +39,10 @@ use substratee_worker_primitives::{BlockHash as SidechainBlockHash, TrustedOpera
 pub struct Watcher<H> {
     //receiver: TracingUnboundedReceiver<TrustedOperationStatus<H, BH>>,
     hash: H,
+    /// shard the operation has been submitted to
+    shard: ShardIdentifier,
+    /// latest status reported to the client
+    status: Option<TrustedOperationStatus>,
     is_in_block: bool,
 }
 
@@ -46,11 +52,13 @@ impl<H: hash::Hash + Encode + traits::Member> Watcher<H> {
         &self.hash
     }
 
-    pub fn new_watcher(hash: H) -> Watcher<H> {
+    pub fn new_watcher(hash: H, shard: ShardIdentifier) -> Watcher<H> {
         //let (sender, receiver) = tracing_unbounded("mpsc_txpool_watcher");
         //self.receivers.push(sender);
         Watcher {
             hash,
+            shard,
+            status: None,
             is_in_block: false,
         }
     }
@@ -105,6 +113,13 @@ impl<H: hash::Hash + Encode + traits::Member> Watcher<H> {
         self.is_in_block = true;
     }
 
+    /// TrustedOperation has been rejected by the STF, for the given reason.
+    pub fn execution_failed(&mut self, reason: String) {
+        self.send(TrustedOperationStatus::ExecutionFailed(reason));
+        // the client stops watching, so the removal from the pool must not be reported
+        self.is_in_block = true;
+    }
+
     /// TrustedOperation has been dropped from the pool because of the limit.
     pub fn dropped(&mut self) {
         self.send(TrustedOperationStatus::Dropped);
@@ -122,8 +137,14 @@ impl<H: hash::Hash + Encode + traits::Member> Watcher<H> {
         self.is_in_block // || self.receivers.is_empty()
     }
 
+    /// Returns the latest status reported to the client.
+    pub fn status(&self) -> Option<&TrustedOperationStatus> {
+        self.status.as_ref()
+    }
+
     fn send(&mut self, status: TrustedOperationStatus) {
-        worker_api_direct::update_status_event(self.hash(), status).unwrap();
+        worker_api_direct::update_status_event(self.hash(), self.shard, status.clone()).unwrap();
+        self.status = Some(status);
     }
 }
 
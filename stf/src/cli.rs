@@ -16,7 +16,8 @@
 */
 
 use crate::{
-    AccountId, Index, KeyPair, ShardIdentifier, TrustedCall, TrustedGetter, TrustedOperation,
+    AccountId, AssetId, Hash, Index, KeyPair, ShardIdentifier, StfEvent, TrustedCall,
+    TrustedGetter, TrustedOperation, NATIVE_ASSET_ID,
 };
 use base58::{FromBase58, ToBase58};
 use clap::{AppSettings, Arg, ArgMatches};
@@ -73,6 +74,15 @@ pub fn cmd<'a>(
                         .global(true)
                         .help("insert if direct invocation call is desired"),
                 )
+                .arg(
+                    Arg::with_name("asset")
+                        .long("asset")
+                        .global(true)
+                        .takes_value(true)
+                        .value_name("U32")
+                        .default_value("0")
+                        .help("asset of balance calls and getters, 0 is the native token"),
+                )
                 .name("substratee-client")
                 .version(VERSION)
                 .author("Supercomputing Systems AG <info@scs.ch>")
@@ -178,13 +188,14 @@ pub fn cmd<'a>(
                         0
                     };
                     debug!("got nonce: {:?}", nonce);
-                    let top: TrustedOperation = TrustedCall::balance_transfer(
-                        sr25519_core::Public::from(from.public()).into(),
-                        to,
-                        amount,
-                    )
-                    .sign(&KeyPair::Sr25519(key_pair), nonce, &mrenclave, &shard)
-                    .into_trusted_operation(direct);
+                    let from_account = sr25519_core::Public::from(from.public()).into();
+                    let call = match get_asset_id(matches) {
+                        NATIVE_ASSET_ID => TrustedCall::balance_transfer(from_account, to, amount),
+                        asset_id => TrustedCall::asset_transfer(from_account, to, asset_id, amount),
+                    };
+                    let top: TrustedOperation = call
+                        .sign(&KeyPair::Sr25519(key_pair), nonce, &mrenclave, &shard)
+                        .into_trusted_operation(direct);
                     let _ = perform_operation(matches, &top);
                     Ok(())
                 }),
@@ -271,11 +282,12 @@ pub fn cmd<'a>(
                     info!("arg_who = {:?}", arg_who);
                     let who = get_pair_from_str(matches, arg_who);
                     let key_pair = sr25519_core::Pair::from(who.clone());
-                    let top: TrustedOperation = TrustedGetter::free_balance(
-                        sr25519_core::Public::from(who.public()).into(),
-                    )
-                    .sign(&KeyPair::Sr25519(key_pair))
-                    .into();
+                    let who_account = sr25519_core::Public::from(who.public()).into();
+                    let getter = match get_asset_id(matches) {
+                        NATIVE_ASSET_ID => TrustedGetter::free_balance(who_account),
+                        asset_id => TrustedGetter::asset_free_balance(who_account, asset_id),
+                    };
+                    let top: TrustedOperation = getter.sign(&KeyPair::Sr25519(key_pair)).into();
                     let res = perform_operation(matches, &top);
                     let bal = if let Some(v) = res {
                         if let Ok(vd) = crate::Balance::decode(&mut v.as_slice()) {
@@ -291,6 +303,53 @@ pub fn cmd<'a>(
                     Ok(())
                 }),
         )
+        .add_cmd(
+            Command::new("events")
+                .description("query the events of an incognito account in keystore in a sidechain block")
+                .options(|app| {
+                    app.arg(
+                        Arg::with_name("accountid")
+                            .takes_value(true)
+                            .required(true)
+                            .value_name("SS58")
+                            .help("AccountId in ss58check format"),
+                    )
+                    .arg(
+                        Arg::with_name("block")
+                            .takes_value(true)
+                            .required(true)
+                            .value_name("U64")
+                            .help("number of the sidechain block"),
+                    )
+                })
+                .runner(move |_args: &str, matches: &ArgMatches<'_>| {
+                    let arg_who = matches.value_of("accountid").unwrap();
+                    let block_number = matches
+                        .value_of("block")
+                        .unwrap()
+                        .parse()
+                        .expect("block number must be a number");
+                    let who = get_pair_from_str(matches, arg_who);
+                    let key_pair = sr25519_core::Pair::from(who.clone());
+                    let top: TrustedOperation = TrustedGetter::events(
+                        sr25519_core::Public::from(who.public()).into(),
+                        block_number,
+                    )
+                    .sign(&KeyPair::Sr25519(key_pair))
+                    .into();
+                    let res = perform_operation(matches, &top);
+                    let events = res
+                        .and_then(|v| Vec::<(Hash, Vec<StfEvent>)>::decode(&mut v.as_slice()).ok())
+                        .unwrap_or_default();
+                    for (call_hash, call_events) in events {
+                        println!("call {:?}:", call_hash);
+                        for event in call_events {
+                            println!("  {:?}", event);
+                        }
+                    }
+                    Ok(())
+                }),
+        )
         .add_cmd(
             Command::new("unshield-funds")
                 .description("Transfer funds from an incognito account to an on-chain account")
@@ -361,14 +420,18 @@ pub fn cmd<'a>(
                         0
                     };
                     debug!("got nonce: {:?}", nonce);
-                    let top: TrustedOperation = TrustedCall::balance_unshield(
-                        sr25519_core::Public::from(from.public()).into(),
-                        to,
-                        amount,
-                        shard,
-                    )
-                    .sign(&KeyPair::Sr25519(key_pair), nonce, &mrenclave, &shard)
-                    .into_trusted_operation(direct);
+                    let from_account = sr25519_core::Public::from(from.public()).into();
+                    let call = match get_asset_id(matches) {
+                        NATIVE_ASSET_ID => {
+                            TrustedCall::balance_unshield(from_account, to, amount, shard)
+                        }
+                        asset_id => {
+                            TrustedCall::asset_unshield(from_account, to, asset_id, amount, shard)
+                        }
+                    };
+                    let top: TrustedOperation = call
+                        .sign(&KeyPair::Sr25519(key_pair), nonce, &mrenclave, &shard)
+                        .into_trusted_operation(direct);
                     let _ = perform_operation(matches, &top);
                     Ok(())
                 }),
@@ -402,6 +465,11 @@ pub fn get_identifiers(matches: &ArgMatches<'_>) -> ([u8; 32], ShardIdentifier)
     (mrenclave, shard)
 }
 
+fn get_asset_id(matches: &ArgMatches<'_>) -> AssetId {
+    u32::from_str_radix(matches.value_of("asset").unwrap(), 10)
+        .expect("asset can be converted to u32")
+}
+
 // TODO this function is redundant with client::main
 fn get_accountid_from_str(account: &str) -> AccountId {
     match &account[..2] {
@@ -0,0 +1,89 @@
+/*
+    Copyright 2019 Supercomputing Systems AG
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+
+*/
+
+//! Events of the executed calls, stored per sidechain block.
+//!
+//! The events of a call are stored under the sidechain block which includes its state changes,
+//! i.e. the one following the current block. They are kept for the last `EVENT_HISTORY_LENGTH`
+//! blocks only.
+
+use sgx_tstd as std;
+use std::prelude::v1::*;
+
+use codec::{Decode, Encode};
+use sp_core::H256 as Hash;
+use support::metadata::StorageHasher;
+
+use crate::sgx::{storage_map_key, storage_value_key};
+use crate::{SidechainBlockNumber, StfEvent};
+
+/// number of sidechain blocks whose events are kept
+pub const EVENT_HISTORY_LENGTH: SidechainBlockNumber = 100;
+
+/// Stores the events of a successfully executed call, identified by its hash
+pub fn deposit_call_events(call_hash: Hash, events: Vec<StfEvent>) {
+    let block_number = current_block_number() + 1;
+    let mut block_events = get_block_events(block_number);
+    if block_events.is_empty() {
+        add_block(block_number);
+    }
+    block_events.push((call_hash, events));
+    sp_io::storage::set(&block_events_key(block_number), &block_events.encode());
+}
+
+/// Returns the hashes and events of the calls of the block, in order of execution
+pub fn get_block_events(block_number: SidechainBlockNumber) -> Vec<(Hash, Vec<StfEvent>)> {
+    sp_io::storage::get(&block_events_key(block_number))
+        .and_then(|events| Vec::<(Hash, Vec<StfEvent>)>::decode(&mut events.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+// remembers the block and prunes the events of the blocks which fell out of the history
+fn add_block(block_number: SidechainBlockNumber) {
+    let mut blocks = get_blocks();
+    let oldest_kept = block_number.saturating_sub(EVENT_HISTORY_LENGTH - 1);
+    blocks.retain(|block| {
+        let keep = *block >= oldest_kept;
+        if !keep {
+            sp_io::storage::clear(&block_events_key(*block));
+        }
+        keep
+    });
+    blocks.push(block_number);
+    sp_io::storage::set(&storage_value_key("Events", "Blocks"), &blocks.encode());
+}
+
+fn get_blocks() -> Vec<SidechainBlockNumber> {
+    sp_io::storage::get(&storage_value_key("Events", "Blocks"))
+        .and_then(|blocks| Vec::<SidechainBlockNumber>::decode(&mut blocks.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+fn current_block_number() -> SidechainBlockNumber {
+    sp_io::storage::get(&storage_value_key("System", "Number"))
+        .and_then(|number| SidechainBlockNumber::decode(&mut number.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+fn block_events_key(block_number: SidechainBlockNumber) -> Vec<u8> {
+    storage_map_key(
+        "Events",
+        "Block",
+        &block_number,
+        &StorageHasher::Blake2_128Concat,
+    )
+}
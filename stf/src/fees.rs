@@ -0,0 +1,146 @@
+/*
+    Copyright 2019 Supercomputing Systems AG
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+
+*/
+
+//! Fee collection of the STF.
+//!
+//! Every call pays the base fee of the `FeeSchedule` in the native asset, except for the root
+//! calls sent by the Sudo key. A batch pays for each of its calls. Shielding is not a call of
+//! an account, it is executed for the deposit on chain and pays no fee. Matched trades pay the
+//! maker and taker fees out of the proceeds of each fill. All fees are credited to the fee
+//! recipient, which defaults to the Sudo key, and are summed up per asset.
+
+use sgx_tstd as std;
+use std::prelude::v1::*;
+
+use codec::{Decode, Encode};
+use log_sgx::*;
+use sgx_runtime::Balance;
+use support::metadata::StorageHasher;
+
+use crate::governance;
+use crate::sgx::{
+    get_asset_balance, set_asset_balance, storage_map_key, storage_value_key, StfError,
+};
+use crate::{AccountId, AssetId, FeeSchedule, TrustedCall, NATIVE_ASSET_ID};
+
+pub fn get_fee_schedule() -> FeeSchedule {
+    sp_io::storage::get(&storage_value_key("Fees", "Schedule"))
+        .and_then(|schedule| FeeSchedule::decode(&mut schedule.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+pub fn set_fee_schedule(schedule: &FeeSchedule) {
+    sp_io::storage::set(&storage_value_key("Fees", "Schedule"), &schedule.encode());
+}
+
+pub fn get_fee_recipient() -> Option<AccountId> {
+    sp_io::storage::get(&storage_value_key("Fees", "Recipient"))
+        .or_else(|| sp_io::storage::get(&storage_value_key("Sudo", "Key")))
+        .and_then(|recipient| AccountId::decode(&mut recipient.as_slice()).ok())
+}
+
+pub fn set_fee_recipient(recipient: &AccountId) {
+    sp_io::storage::set(&storage_value_key("Fees", "Recipient"), &recipient.encode());
+}
+
+/// Returns the total of the fees collected in the asset
+pub fn get_accrued_fees(asset_id: AssetId) -> Balance {
+    sp_io::storage::get(&accrued_fees_key(asset_id))
+        .and_then(|fees| Balance::decode(&mut fees.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the base fee the call has to pay
+///
+/// Root calls are only free if root sends them. A batch pays the base fees of all its calls.
+pub fn call_fee(call: &TrustedCall) -> Balance {
+    match call {
+        TrustedCall::balance_set_balance(sender, _, _, _) | TrustedCall::sudo(sender, _, _)
+            if governance::get_sudo_key().as_ref() == Some(sender) =>
+        {
+            0
+        }
+        TrustedCall::batch(_, calls) => calls.iter().fold(0, |total: Balance, call| {
+            total.saturating_add(call_fee(call))
+        }),
+        _ => get_fee_schedule().base_fee,
+    }
+}
+
+/// Charges the base fee of the call to its sender and returns the amount charged
+pub fn charge_call_fee(who: &AccountId, call: &TrustedCall) -> Result<Balance, StfError> {
+    let fee = call_fee(call);
+    if fee > 0 {
+        pay_fee(who, NATIVE_ASSET_ID, fee)?;
+        debug!("charged call fee of {}", fee);
+    }
+    Ok(fee)
+}
+
+fn pay_fee(payer: &AccountId, asset_id: AssetId, fee: Balance) -> Result<(), StfError> {
+    let recipient = fee_recipient()?;
+    if get_asset_balance(payer, asset_id).free < fee {
+        return Err(StfError::MissingFeeFunds(fee));
+    }
+    if recipient != *payer {
+        move_free(payer, &recipient, asset_id, fee)?;
+    }
+    accrue_fee(asset_id, fee);
+    Ok(())
+}
+
+/// Returns the account the fees are paid to
+pub fn fee_recipient() -> Result<AccountId, StfError> {
+    get_fee_recipient().ok_or_else(|| StfError::Dispatch("fees::no_recipient".to_string()))
+}
+
+/// Adds a fee paid to the fee recipient to the total collected in the asset
+pub fn accrue_fee(asset_id: AssetId, fee: Balance) {
+    if fee > 0 {
+        let accrued = get_accrued_fees(asset_id).saturating_add(fee);
+        sp_io::storage::set(&accrued_fees_key(asset_id), &accrued.encode());
+    }
+}
+
+fn move_free(
+    from: &AccountId,
+    to: &AccountId,
+    asset_id: AssetId,
+    amount: Balance,
+) -> Result<(), StfError> {
+    let mut from_balance = get_asset_balance(from, asset_id);
+    let mut to_balance = get_asset_balance(to, asset_id);
+    if from_balance.free < amount {
+        return Err(StfError::MissingFunds);
+    }
+    from_balance.free -= amount;
+    to_balance.free = to_balance
+        .free
+        .checked_add(amount)
+        .ok_or_else(|| StfError::Dispatch("fees::overflow".to_string()))?;
+    set_asset_balance(from.clone(), asset_id, from_balance)?;
+    set_asset_balance(to.clone(), asset_id, to_balance)
+}
+
+fn accrued_fees_key(asset_id: AssetId) -> Vec<u8> {
+    storage_map_key(
+        "Fees",
+        "Accrued",
+        &asset_id,
+        &StorageHasher::Blake2_128Concat,
+    )
+}
@@ -0,0 +1,149 @@
+/*
+    Copyright 2019 Supercomputing Systems AG
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+
+*/
+
+//! Shard parameters governed by the Sudo key.
+//!
+//! The native asset is always whitelisted, other assets can only be shielded once they are.
+//! Removing an asset from the whitelist or deregistering a market does not touch existing
+//! balances and orders, it only prevents new shields, markets and orders respectively.
+
+use sgx_tstd as std;
+use std::prelude::v1::*;
+
+use codec::{Decode, Encode};
+use log_sgx::*;
+
+use crate::sgx::{storage_value_key, StfError};
+use crate::{fees, AccountId, AssetId, MarketId, SudoCall, TrustedCall, NATIVE_ASSET_ID};
+
+pub fn get_sudo_key() -> Option<AccountId> {
+    sp_io::storage::get(&storage_value_key("Sudo", "Key"))
+        .and_then(|key| AccountId::decode(&mut key.as_slice()).ok())
+}
+
+pub fn ensure_root(account: AccountId) -> Result<(), StfError> {
+    if get_sudo_key().as_ref() == Some(&account) {
+        Ok(())
+    } else {
+        Err(StfError::MissingPrivileges(account))
+    }
+}
+
+/// Executes the Sudo call, whose sender must have been checked to be root
+pub fn dispatch(call: SudoCall) -> Result<(), StfError> {
+    match call {
+        SudoCall::set_key(new_key) => {
+            sp_io::storage::set(&storage_value_key("Sudo", "Key"), &new_key.encode())
+        }
+        SudoCall::set_fee_schedule(schedule) => fees::set_fee_schedule(&schedule),
+        SudoCall::set_fee_recipient(recipient) => fees::set_fee_recipient(&recipient),
+        SudoCall::register_market(market) => {
+            if market.base == market.quote
+                || !is_asset_whitelisted(market.base)
+                || !is_asset_whitelisted(market.quote)
+            {
+                return Err(StfError::InvalidMarket(market));
+            }
+            let mut markets = get_markets();
+            if !markets.contains(&market) {
+                markets.push(market);
+                put_markets(&markets);
+            }
+        }
+        SudoCall::deregister_market(market) => {
+            let mut markets = get_markets();
+            markets.retain(|registered| *registered != market);
+            put_markets(&markets);
+        }
+        SudoCall::whitelist_asset(asset_id) => {
+            let mut whitelist = get_asset_whitelist();
+            if !whitelist.contains(&asset_id) {
+                whitelist.push(asset_id);
+                put_asset_whitelist(&whitelist);
+            }
+        }
+        SudoCall::remove_whitelisted_asset(asset_id) => {
+            let mut whitelist = get_asset_whitelist();
+            whitelist.retain(|whitelisted| *whitelisted != asset_id);
+            put_asset_whitelist(&whitelist);
+        }
+        SudoCall::pause_shard => set_paused(true),
+        SudoCall::resume_shard => set_paused(false),
+    };
+    Ok(())
+}
+
+pub fn is_paused() -> bool {
+    sp_io::storage::get(&storage_value_key("Governance", "Paused"))
+        .and_then(|paused| bool::decode(&mut paused.as_slice()).ok())
+        .unwrap_or(false)
+}
+
+/// Rejects the call if the shard is paused
+///
+/// Only root can still govern the shard. Shields are not checked, they are executed for
+/// their deposit on chain even while the shard is paused. A batch is only executed if all of
+/// its calls would be.
+pub fn ensure_not_paused(call: &TrustedCall) -> Result<(), StfError> {
+    match call {
+        TrustedCall::sudo(_, _, _) => Ok(()),
+        TrustedCall::batch(_, calls) => calls.iter().try_for_each(ensure_not_paused),
+        _ if is_paused() => Err(StfError::ShardPaused),
+        _ => Ok(()),
+    }
+}
+
+fn set_paused(paused: bool) {
+    debug!("setting shard paused to {}", paused);
+    sp_io::storage::set(&storage_value_key("Governance", "Paused"), &paused.encode());
+}
+
+/// Returns the markets in which orders can be placed
+pub fn get_markets() -> Vec<MarketId> {
+    sp_io::storage::get(&storage_value_key("Governance", "Markets"))
+        .and_then(|markets| Vec::<MarketId>::decode(&mut markets.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+pub fn is_market_registered(market: &MarketId) -> bool {
+    get_markets().contains(market)
+}
+
+fn put_markets(markets: &[MarketId]) {
+    sp_io::storage::set(
+        &storage_value_key("Governance", "Markets"),
+        &markets.encode(),
+    );
+}
+
+/// Returns the non-native assets which can be traded
+pub fn get_asset_whitelist() -> Vec<AssetId> {
+    sp_io::storage::get(&storage_value_key("Governance", "AssetWhitelist"))
+        .and_then(|whitelist| Vec::<AssetId>::decode(&mut whitelist.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+pub fn is_asset_whitelisted(asset_id: AssetId) -> bool {
+    asset_id == NATIVE_ASSET_ID || get_asset_whitelist().contains(&asset_id)
+}
+
+fn put_asset_whitelist(whitelist: &[AssetId]) {
+    sp_io::storage::set(
+        &storage_value_key("Governance", "AssetWhitelist"),
+        &whitelist.encode(),
+    );
+}
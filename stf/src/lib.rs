@@ -28,6 +28,7 @@ extern crate alloc;
 #[cfg(feature = "std")]
 extern crate clap;
 
+use alloc::vec::Vec;
 use codec::{Compact, Decode, Encode};
 #[cfg(feature = "std")]
 use my_node_runtime::Balance;
@@ -41,7 +42,7 @@ pub use sgx_runtime::Index;
 use sp_core::crypto::AccountId32;
 //use sp_core::{Encode, Decode};
 use sp_core::{ed25519, sr25519, Pair, H256};
-use sp_runtime::{traits::Verify, MultiSignature};
+use sp_runtime::{traits::Verify, MultiSignature, Permill};
 // TODO: use MultiAddress instead of AccountId32?
 
 //pub type Signature = AnySignature;
@@ -59,8 +60,155 @@ pub static UNSHIELD: u8 = 6u8;
 //pub static CALL_CONFIRMED: u8 = 3u8;
 
 pub type ShardIdentifier = H256;
+/// Number of a sidechain block
+pub type SidechainBlockNumber = u64;
 //pub type Index = u32;
 
+/// Identifier of an asset held in the STF
+pub type AssetId = u32;
+/// The native token, which is held by the balances pallet
+pub const NATIVE_ASSET_ID: AssetId = 0;
+
+pub type OrderId = u64;
+
+/// Pair of assets traded against each other
+///
+/// Quantities are denominated in the base asset, prices in units of the quote asset per
+/// unit of the base asset.
+#[derive(Encode, Decode, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MarketId {
+    pub base: AssetId,
+    pub quote: AssetId,
+}
+
+#[derive(Encode, Decode, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderSide {
+    /// buy the base asset
+    Bid,
+    /// sell the base asset
+    Ask,
+}
+
+impl OrderSide {
+    pub fn opposite(&self) -> Self {
+        match self {
+            OrderSide::Bid => OrderSide::Ask,
+            OrderSide::Ask => OrderSide::Bid,
+        }
+    }
+}
+
+#[derive(Encode, Decode, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrderType {
+    /// matches up to its price, the remainder rests in the order book
+    Limit,
+    /// matches at any price, the remainder is dropped
+    Market,
+}
+
+/// Order resting in the order book of a market
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct Order {
+    pub id: OrderId,
+    pub owner: AccountId,
+    pub market: MarketId,
+    pub side: OrderSide,
+    pub price: Balance,
+    /// base quantity which has not been filled yet
+    pub quantity: Balance,
+}
+
+/// Fill of a resting (maker) order by an incoming (taker) order
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct Trade {
+    pub market: MarketId,
+    pub maker_order_id: OrderId,
+    pub taker_order_id: OrderId,
+    pub maker: AccountId,
+    pub taker: AccountId,
+    pub taker_side: OrderSide,
+    /// price of the maker order
+    pub price: Balance,
+    pub quantity: Balance,
+    /// fee paid by the maker, in the asset it received
+    pub maker_fee: Balance,
+    /// fee paid by the taker, in the asset it received
+    pub taker_fee: Balance,
+}
+
+/// Open base quantity of all orders at one price
+#[derive(Encode, Decode, Clone, Debug, PartialEq, Eq)]
+pub struct PriceLevel {
+    pub price: Balance,
+    pub quantity: Balance,
+}
+
+/// Order book of a market aggregated by price, best price first
+#[derive(Encode, Decode, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MarketDepth {
+    pub bids: Vec<PriceLevel>,
+    pub asks: Vec<PriceLevel>,
+}
+
+/// Fees charged by the STF, which are credited to the fee recipient of the shard
+#[derive(Encode, Decode, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeeSchedule {
+    /// paid in the native asset for every call which is not issued by the chain or by root
+    pub base_fee: Balance,
+    /// share of the proceeds of a fill paid by the owner of the resting order
+    pub maker_fee: Permill,
+    /// share of the proceeds of a fill paid by the owner of the incoming order
+    pub taker_fee: Permill,
+}
+
+/// What happened during the execution of a trusted call
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+pub enum StfEvent {
+    BalanceSet(AccountId, Balance, Balance), // (Who, Free, Reserved)
+    Transferred(AccountId, AccountId, AssetId, Balance), // (From, To, Asset, Amount)
+    Shielded(AccountId, AssetId, Balance),   // (AccountIncognito, Asset, Amount)
+    Unshielded(AccountId, AccountId, AssetId, Balance), // (AccountIncognito, BeneficiaryPublicAccount, Asset, Amount)
+    OrderPlaced(AccountId, MarketId, OrderId),          // (Owner, Market, assigned Order)
+    OrderFilled(Trade),
+    OrderCancelled(AccountId, MarketId, OrderId), // (Owner, Market, Order)
+    CallFeePaid(AccountId, Balance),              // (Sender, Fee)
+    SudoExecuted(SudoCall),
+}
+
+impl StfEvent {
+    /// Returns true if the account is a party of the event
+    pub fn involves(&self, who: &AccountId) -> bool {
+        match self {
+            StfEvent::BalanceSet(account, _, _)
+            | StfEvent::Shielded(account, _, _)
+            | StfEvent::OrderPlaced(account, _, _)
+            | StfEvent::OrderCancelled(account, _, _)
+            | StfEvent::CallFeePaid(account, _) => account == who,
+            StfEvent::Transferred(from, to, _, _) | StfEvent::Unshielded(from, to, _, _) => {
+                from == who || to == who
+            }
+            StfEvent::OrderFilled(trade) => trade.maker == *who || trade.taker == *who,
+            StfEvent::SudoExecuted(_) => false,
+        }
+    }
+}
+
+/// Initial state of a shard, sealed into the shard when it is initialized
+#[derive(Encode, Decode, Clone, Debug, Default, PartialEq)]
+pub struct GenesisConfig {
+    /// a shard without Sudo key can not be governed
+    pub sudo: Option<AccountId>,
+    /// free balances in the native asset
+    pub balances: Vec<(AccountId, Balance)>,
+    /// whitelisted non-native assets
+    pub assets: Vec<AssetId>,
+    /// free balances in non-native assets
+    pub asset_balances: Vec<(AccountId, AssetId, Balance)>,
+}
+
+/// Version of the STF as reported by the `system_version` rpc method
+pub const STF_VERSION: &str = env!("CARGO_PKG_VERSION");
+
 #[derive(Clone)]
 pub enum KeyPair {
     Sr25519(sr25519::Pair),
@@ -88,6 +236,14 @@ impl From<sr25519::Pair> for KeyPair {
     }
 }
 
+#[cfg(feature = "sgx")]
+pub mod events;
+#[cfg(feature = "sgx")]
+pub mod fees;
+#[cfg(feature = "sgx")]
+pub mod governance;
+#[cfg(feature = "sgx")]
+pub mod order_book;
 #[cfg(feature = "sgx")]
 pub mod sgx;
 
@@ -156,7 +312,29 @@ impl From<TrustedGetterSigned> for Getter {
 #[derive(Encode, Decode, Clone, Debug)]
 #[allow(non_camel_case_types)]
 pub enum PublicGetter {
-    some_value,
+    market_depth(MarketId),
+    last_price(MarketId),
+}
+
+/// Administration of a shard by its Sudo key
+///
+/// Every executed call is confirmed on chain with its encoding, so that governance actions
+/// can be audited.
+#[derive(Encode, Decode, Clone, Debug, PartialEq)]
+#[allow(non_camel_case_types)]
+pub enum SudoCall {
+    set_key(AccountId),
+    set_fee_schedule(FeeSchedule),
+    set_fee_recipient(AccountId),
+    /// only whitelisted assets can be traded against each other
+    register_market(MarketId),
+    /// resting orders can still be cancelled
+    deregister_market(MarketId),
+    whitelist_asset(AssetId),
+    remove_whitelisted_asset(AssetId),
+    /// only shielding and Sudo calls are executed while the shard is paused
+    pause_shard,
+    resume_shard,
 }
 
 #[derive(Encode, Decode, Clone, Debug)]
@@ -166,6 +344,13 @@ pub enum TrustedCall {
     balance_transfer(AccountId, AccountId, Balance),
     balance_unshield(AccountId, AccountId, Balance, ShardIdentifier), // (AccountIncognito, BeneficiaryPublicAccount, Amount, Shard)
     balance_shield(AccountId, Balance),                               // (AccountIncognito, Amount)
+    asset_transfer(AccountId, AccountId, AssetId, Balance),
+    asset_unshield(AccountId, AccountId, AssetId, Balance, ShardIdentifier), // (AccountIncognito, BeneficiaryPublicAccount, Asset, Amount, Shard)
+    asset_shield(AccountId, AssetId, Balance), // (AccountIncognito, Asset, Amount)
+    place_order(AccountId, MarketId, OrderSide, OrderType, Balance, Balance), // (Owner, Market, Side, Type, Price, Quantity)
+    cancel_order(AccountId, MarketId, OrderId),
+    sudo(AccountId, SudoCall, ShardIdentifier), // (Root, Call, Shard)
+    batch(AccountId, Vec<TrustedCall>), // (Sender, Calls of the sender, executed all-or-nothing)
 }
 
 impl TrustedCall {
@@ -175,6 +360,21 @@ impl TrustedCall {
             TrustedCall::balance_transfer(account, _, _) => account,
             TrustedCall::balance_unshield(account, _, _, _) => account,
             TrustedCall::balance_shield(account, _) => account,
+            TrustedCall::asset_transfer(account, _, _, _) => account,
+            TrustedCall::asset_unshield(account, _, _, _, _) => account,
+            TrustedCall::asset_shield(account, _, _) => account,
+            TrustedCall::place_order(account, _, _, _, _, _) => account,
+            TrustedCall::cancel_order(account, _, _) => account,
+            TrustedCall::sudo(account, _, _) => account,
+            TrustedCall::batch(account, _) => account,
+        }
+    }
+
+    /// Returns the shard the call is bound to, if it names one
+    pub fn shard(&self) -> Option<&ShardIdentifier> {
+        match self {
+            TrustedCall::sudo(_, _, shard) => Some(shard),
+            _ => None,
         }
     }
 
@@ -204,6 +404,15 @@ pub enum TrustedGetter {
     free_balance(AccountId),
     reserved_balance(AccountId),
     nonce(AccountId),
+    asset_free_balance(AccountId, AssetId),
+    asset_reserved_balance(AccountId, AssetId),
+    open_orders(AccountId),
+    order(AccountId, OrderId),
+    trade_history(AccountId),
+    order_reserved_balance(AccountId, AssetId),
+    accrued_fees(AccountId, AssetId),
+    /// events of the calls in the block, root gets those which do not involve it as well
+    events(AccountId, SidechainBlockNumber),
 }
 
 impl TrustedGetter {
@@ -212,6 +421,14 @@ impl TrustedGetter {
             TrustedGetter::free_balance(account) => account,
             TrustedGetter::reserved_balance(account) => account,
             TrustedGetter::nonce(account) => account,
+            TrustedGetter::asset_free_balance(account, _) => account,
+            TrustedGetter::asset_reserved_balance(account, _) => account,
+            TrustedGetter::open_orders(account) => account,
+            TrustedGetter::order(account, _) => account,
+            TrustedGetter::trade_history(account) => account,
+            TrustedGetter::order_reserved_balance(account, _) => account,
+            TrustedGetter::accrued_fees(account, _) => account,
+            TrustedGetter::events(account, _) => account,
         }
     }
 
@@ -274,15 +491,6 @@ impl TrustedCallSigned {
     }
 }
 
-// TODO: #91 signed return value
-/*
-pub struct TrustedReturnValue<T> {
-    pub value: T,
-    pub signer: AccountId
-}
-impl TrustedReturnValue
-*/
-
 #[cfg(feature = "sgx")]
 pub struct Stf {}
 
@@ -0,0 +1,582 @@
+/*
+    Copyright 2019 Supercomputing Systems AG
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+
+*/
+
+//! Order books of the markets of a shard and their matching engine.
+//!
+//! Every resting order is stored by its market and id. Each side of a market's book keeps the
+//! prices at which orders rest, best price first, and every price level keeps the ids of its
+//! orders, oldest first. Order ids increase monotonically, so they double as the time of
+//! placement. Matching thus only reads and writes the orders and price levels it touches.
+//!
+//! Resting orders lock their funds in the reserved balance of their owner: bids the quote
+//! amount at their limit price, asks the base quantity. Fills are settled against these
+//! reserved balances at the price of the resting order.
+//!
+//! Next to the books, the shard state indexes the open orders and the most recent trades of
+//! every account as well as the last traded price of every market, which are served by the
+//! order getters.
+
+use sgx_tstd as std;
+use std::prelude::v1::*;
+
+use codec::{Decode, Encode};
+use log_sgx::*;
+use sgx_runtime::Balance;
+use support::metadata::StorageHasher;
+
+use crate::sgx::{
+    get_asset_balance, set_asset_balance, storage_double_map_key, storage_map_key,
+    storage_value_key, AssetBalance, StfError,
+};
+use crate::{fees, governance};
+use crate::{
+    AccountId, AssetId, MarketDepth, MarketId, Order, OrderId, OrderSide, OrderType, PriceLevel,
+    Trade,
+};
+
+/// Number of most recent trades kept per account
+pub const MAX_TRADE_HISTORY: usize = 100;
+
+/// Places an order, matches it against the opposite side of the book and rests the
+/// unfilled remainder of a limit order
+///
+/// Returns the id assigned to the order and the resulting trades.
+pub fn place_order(
+    owner: AccountId,
+    market: MarketId,
+    side: OrderSide,
+    order_type: OrderType,
+    price: Balance,
+    quantity: Balance,
+) -> Result<(OrderId, Vec<Trade>), StfError> {
+    if !governance::is_market_registered(&market) {
+        return Err(StfError::InvalidMarket(market));
+    }
+    if quantity == 0 {
+        return Err(StfError::InvalidOrder(
+            "quantity must not be zero".to_string(),
+        ));
+    }
+    if order_type == OrderType::Limit && price == 0 {
+        return Err(StfError::InvalidOrder(
+            "limit price must not be zero".to_string(),
+        ));
+    }
+
+    // the owner must be able to pay for the whole order up front. A market bid has no
+    // price, it is limited by the free quote balance while matching instead.
+    match (side, order_type) {
+        (OrderSide::Bid, OrderType::Limit) => {
+            ensure_free_balance(&owner, market.quote, quote_amount(price, quantity)?)?
+        }
+        (OrderSide::Ask, _) => ensure_free_balance(&owner, market.base, quantity)?,
+        (OrderSide::Bid, OrderType::Market) => (),
+    };
+
+    let id = next_order_id();
+    let mut remaining = quantity;
+    let mut trades = Vec::new();
+
+    'levels: for level_price in get_price_levels(&market, side.opposite()) {
+        if order_type == OrderType::Limit && !crosses(side, price, level_price) {
+            break;
+        }
+        for maker_id in get_level_orders(&market, side.opposite(), level_price) {
+            if remaining == 0 {
+                break 'levels;
+            }
+            let mut maker = find_order(&market, maker_id)
+                .ok_or_else(|| StfError::Dispatch("order_book::missing_order".to_string()))?;
+            let mut fill = remaining.min(maker.quantity);
+            if side == OrderSide::Bid && order_type == OrderType::Market {
+                let affordable = get_asset_balance(&owner, market.quote).free / maker.price;
+                fill = fill.min(affordable);
+                if fill == 0 {
+                    break 'levels;
+                }
+            }
+
+            let (maker_fee, taker_fee) = settle_fill(&owner, &maker, fill)?;
+            maker.quantity -= fill;
+            remaining -= fill;
+            if maker.quantity == 0 {
+                remove_order(&maker);
+            } else {
+                put_order(&maker);
+            }
+            let trade = Trade {
+                market,
+                maker_order_id: maker.id,
+                taker_order_id: id,
+                maker: maker.owner.clone(),
+                taker: owner.clone(),
+                taker_side: side,
+                price: maker.price,
+                quantity: fill,
+                maker_fee,
+                taker_fee,
+            };
+            record_trade(&trade);
+            trades.push(trade);
+        }
+    }
+
+    if order_type == OrderType::Limit && remaining > 0 {
+        let order = Order {
+            id,
+            owner,
+            market,
+            side,
+            price,
+            quantity: remaining,
+        };
+        let (asset_id, amount) = locked_funds(&order)?;
+        reserve(&order.owner, asset_id, amount)?;
+        insert_order(order);
+    }
+
+    debug!(
+        "placed order {} in market {:?} with {} fills",
+        id,
+        market,
+        trades.len()
+    );
+    Ok((id, trades))
+}
+
+/// Removes the resting order from the book and releases its reserved funds
+pub fn cancel_order(owner: &AccountId, market: &MarketId, id: OrderId) -> Result<(), StfError> {
+    let order = find_order(market, id).ok_or(StfError::InexistentOrder(id))?;
+    if order.owner != *owner {
+        return Err(StfError::MissingPrivileges(owner.clone()));
+    }
+    let (asset_id, amount) = locked_funds(&order)?;
+    unreserve(owner, asset_id, amount)?;
+    remove_order(&order);
+    debug!("cancelled order {} in market {:?}", id, market);
+    Ok(())
+}
+
+/// Returns the resting orders of one side of the market's book in price-time priority
+pub fn get_orders(market: &MarketId, side: OrderSide) -> Vec<Order> {
+    get_price_levels(market, side)
+        .into_iter()
+        .flat_map(|price| get_level_orders(market, side, price))
+        .filter_map(|id| find_order(market, id))
+        .collect()
+}
+
+/// Returns the open orders of the account in the order they were placed
+pub fn get_open_orders(owner: &AccountId) -> Vec<Order> {
+    get_account_orders(owner)
+        .into_iter()
+        .filter_map(|(market, id)| find_order(&market, id))
+        .collect()
+}
+
+/// Returns the open order with the given id if it belongs to the account
+pub fn get_order(owner: &AccountId, id: OrderId) -> Option<Order> {
+    get_account_orders(owner)
+        .into_iter()
+        .find(|(_, order_id)| *order_id == id)
+        .and_then(|(market, id)| find_order(&market, id))
+}
+
+/// Returns the most recent trades of the account, oldest first
+pub fn get_trade_history(who: &AccountId) -> Vec<Trade> {
+    sp_io::storage::get(&account_trades_key(who))
+        .and_then(|trades| Vec::<Trade>::decode(&mut trades.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the amount of the asset the account has reserved for its open orders
+pub fn get_order_reserved_balance(owner: &AccountId, asset_id: AssetId) -> Balance {
+    get_open_orders(owner)
+        .iter()
+        .filter_map(|order| locked_funds(order).ok())
+        .filter(|(locked_asset_id, _)| *locked_asset_id == asset_id)
+        .fold(0, |total, (_, amount)| total.saturating_add(amount))
+}
+
+/// Returns the open quantity of the market's book aggregated by price
+pub fn get_market_depth(market: &MarketId) -> MarketDepth {
+    MarketDepth {
+        bids: get_depth(market, OrderSide::Bid),
+        asks: get_depth(market, OrderSide::Ask),
+    }
+}
+
+/// Returns the price of the latest trade in the market
+pub fn get_last_price(market: &MarketId) -> Option<Balance> {
+    sp_io::storage::get(&last_price_key(market))
+        .and_then(|price| Balance::decode(&mut price.as_slice()).ok())
+}
+
+fn find_order(market: &MarketId, id: OrderId) -> Option<Order> {
+    sp_io::storage::get(&order_key(market, id))
+        .and_then(|order| Order::decode(&mut order.as_slice()).ok())
+}
+
+fn put_order(order: &Order) {
+    sp_io::storage::set(&order_key(&order.market, order.id), &order.encode());
+}
+
+fn insert_order(order: Order) {
+    let (market, side, price) = (order.market, order.side, order.price);
+    let mut level = get_level_orders(&market, side, price);
+    if level.is_empty() {
+        // behind all levels with the same or a better price
+        let mut prices = get_price_levels(&market, side);
+        let index = prices
+            .iter()
+            .position(|resting| is_better_price(side, price, *resting))
+            .unwrap_or_else(|| prices.len());
+        prices.insert(index, price);
+        put_list(&price_levels_key(&market, side), &prices);
+    }
+    level.push(order.id);
+    put_list(&level_orders_key(&market, side, price), &level);
+    let mut account_orders = get_account_orders(&order.owner);
+    account_orders.push((market, order.id));
+    put_account_orders(&order.owner, &account_orders);
+    put_order(&order);
+}
+
+fn remove_order(order: &Order) {
+    let (market, side, price) = (order.market, order.side, order.price);
+    let mut level = get_level_orders(&market, side, price);
+    level.retain(|id| *id != order.id);
+    put_list(&level_orders_key(&market, side, price), &level);
+    if level.is_empty() {
+        let mut prices = get_price_levels(&market, side);
+        prices.retain(|resting| *resting != price);
+        put_list(&price_levels_key(&market, side), &prices);
+    }
+    remove_account_order(&order.owner, order.id);
+    sp_io::storage::clear(&order_key(&market, order.id));
+}
+
+/// Returns the prices at which orders rest on one side of the market's book, best price first
+fn get_price_levels(market: &MarketId, side: OrderSide) -> Vec<Balance> {
+    sp_io::storage::get(&price_levels_key(market, side))
+        .and_then(|prices| Vec::<Balance>::decode(&mut prices.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the ids of the orders resting at the price, oldest first
+fn get_level_orders(market: &MarketId, side: OrderSide, price: Balance) -> Vec<OrderId> {
+    sp_io::storage::get(&level_orders_key(market, side, price))
+        .and_then(|ids| Vec::<OrderId>::decode(&mut ids.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+fn get_depth(market: &MarketId, side: OrderSide) -> Vec<PriceLevel> {
+    get_price_levels(market, side)
+        .into_iter()
+        .map(|price| PriceLevel {
+            price,
+            quantity: get_level_orders(market, side, price)
+                .into_iter()
+                .filter_map(|id| find_order(market, id))
+                .fold(0, |total: Balance, order| {
+                    total.saturating_add(order.quantity)
+                }),
+        })
+        .collect()
+}
+
+fn put_list<T: Encode>(key: &[u8], list: &[T]) {
+    if list.is_empty() {
+        sp_io::storage::clear(key);
+    } else {
+        sp_io::storage::set(key, &list.encode());
+    }
+}
+
+fn get_account_orders(owner: &AccountId) -> Vec<(MarketId, OrderId)> {
+    sp_io::storage::get(&account_orders_key(owner))
+        .and_then(|orders| Vec::<(MarketId, OrderId)>::decode(&mut orders.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+fn put_account_orders(owner: &AccountId, orders: &[(MarketId, OrderId)]) {
+    put_list(&account_orders_key(owner), orders)
+}
+
+fn remove_account_order(owner: &AccountId, id: OrderId) {
+    let mut orders = get_account_orders(owner);
+    orders.retain(|(_, order_id)| *order_id != id);
+    put_account_orders(owner, &orders);
+}
+
+fn record_trade(trade: &Trade) {
+    sp_io::storage::set(&last_price_key(&trade.market), &trade.price.encode());
+    push_trade(&trade.taker, trade);
+    if trade.maker != trade.taker {
+        push_trade(&trade.maker, trade);
+    }
+}
+
+fn push_trade(who: &AccountId, trade: &Trade) {
+    let mut trades = get_trade_history(who);
+    trades.push(trade.clone());
+    if trades.len() > MAX_TRADE_HISTORY {
+        trades.drain(..trades.len() - MAX_TRADE_HISTORY);
+    }
+    sp_io::storage::set(&account_trades_key(who), &trades.encode());
+}
+
+fn order_key(market: &MarketId, id: OrderId) -> Vec<u8> {
+    storage_double_map_key(
+        "OrderBook",
+        "Orders",
+        market,
+        &StorageHasher::Blake2_128Concat,
+        &id,
+        &StorageHasher::Blake2_128Concat,
+    )
+}
+
+fn price_levels_key(market: &MarketId, side: OrderSide) -> Vec<u8> {
+    storage_double_map_key(
+        "OrderBook",
+        "PriceLevels",
+        market,
+        &StorageHasher::Blake2_128Concat,
+        &side,
+        &StorageHasher::Blake2_128Concat,
+    )
+}
+
+fn level_orders_key(market: &MarketId, side: OrderSide, price: Balance) -> Vec<u8> {
+    storage_double_map_key(
+        "OrderBook",
+        "LevelOrders",
+        market,
+        &StorageHasher::Blake2_128Concat,
+        &(side, price),
+        &StorageHasher::Blake2_128Concat,
+    )
+}
+
+fn account_orders_key(owner: &AccountId) -> Vec<u8> {
+    storage_map_key(
+        "OrderBook",
+        "AccountOrders",
+        owner,
+        &StorageHasher::Blake2_128Concat,
+    )
+}
+
+fn account_trades_key(who: &AccountId) -> Vec<u8> {
+    storage_map_key(
+        "OrderBook",
+        "AccountTrades",
+        who,
+        &StorageHasher::Blake2_128Concat,
+    )
+}
+
+fn last_price_key(market: &MarketId) -> Vec<u8> {
+    storage_map_key(
+        "OrderBook",
+        "LastPrice",
+        market,
+        &StorageHasher::Blake2_128Concat,
+    )
+}
+
+fn next_order_id() -> OrderId {
+    let key = storage_value_key("OrderBook", "NextOrderId");
+    let id = sp_io::storage::get(&key)
+        .and_then(|id| OrderId::decode(&mut id.as_slice()).ok())
+        .unwrap_or_default();
+    sp_io::storage::set(&key, &(id + 1).encode());
+    id
+}
+
+/// whether an order of `side` with `price` matches a resting order with `resting_price`
+fn crosses(side: OrderSide, price: Balance, resting_price: Balance) -> bool {
+    match side {
+        OrderSide::Bid => price >= resting_price,
+        OrderSide::Ask => price <= resting_price,
+    }
+}
+
+fn is_better_price(side: OrderSide, price: Balance, other_price: Balance) -> bool {
+    match side {
+        OrderSide::Bid => price > other_price,
+        OrderSide::Ask => price < other_price,
+    }
+}
+
+fn quote_amount(price: Balance, quantity: Balance) -> Result<Balance, StfError> {
+    price
+        .checked_mul(quantity)
+        .ok_or_else(|| StfError::InvalidOrder("quote amount overflows".to_string()))
+}
+
+/// the asset and amount a resting order keeps reserved
+fn locked_funds(order: &Order) -> Result<(AssetId, Balance), StfError> {
+    match order.side {
+        OrderSide::Bid => Ok((
+            order.market.quote,
+            quote_amount(order.price, order.quantity)?,
+        )),
+        OrderSide::Ask => Ok((order.market.base, order.quantity)),
+    }
+}
+
+/// Exchanges `quantity` of the maker order between taker and maker
+///
+/// The taker pays from its free balance, the maker from its reserved balance. Both pay their
+/// trading fee out of what they receive. Returns the fees of maker and taker.
+fn settle_fill(
+    taker: &AccountId,
+    maker: &Order,
+    quantity: Balance,
+) -> Result<(Balance, Balance), StfError> {
+    let market = maker.market;
+    let quote = quote_amount(maker.price, quantity)?;
+    let ((paid_asset, paid), (given_asset, given)) = match maker.side {
+        OrderSide::Ask => ((market.quote, quote), (market.base, quantity)),
+        OrderSide::Bid => ((market.base, quantity), (market.quote, quote)),
+    };
+    let schedule = fees::get_fee_schedule();
+    let maker_fee = schedule.maker_fee * paid;
+    let taker_fee = schedule.taker_fee * given;
+
+    // every balance is checked before any of them is written
+    let mut settlement = Settlement::default();
+    settlement.transfer_free(taker, &maker.owner, paid_asset, paid)?;
+    settlement.transfer_reserved(&maker.owner, taker, given_asset, given)?;
+    if maker_fee > 0 || taker_fee > 0 {
+        let recipient = fees::fee_recipient()?;
+        settlement.transfer_free(&maker.owner, &recipient, paid_asset, maker_fee)?;
+        settlement.transfer_free(taker, &recipient, given_asset, taker_fee)?;
+    }
+    settlement.apply()?;
+    fees::accrue_fee(paid_asset, maker_fee);
+    fees::accrue_fee(given_asset, taker_fee);
+    Ok((maker_fee, taker_fee))
+}
+
+/// Balance changes of a fill, which are only written once all of them are valid
+#[derive(Default)]
+struct Settlement {
+    balances: Vec<(AccountId, AssetId, AssetBalance)>,
+}
+
+impl Settlement {
+    fn balance(&mut self, who: &AccountId, asset_id: AssetId) -> &mut AssetBalance {
+        let index = match self
+            .balances
+            .iter()
+            .position(|(account, asset, _)| account == who && *asset == asset_id)
+        {
+            Some(index) => index,
+            None => {
+                let balance = get_asset_balance(who, asset_id);
+                self.balances.push((who.clone(), asset_id, balance));
+                self.balances.len() - 1
+            }
+        };
+        &mut self.balances[index].2
+    }
+
+    fn transfer_free(
+        &mut self,
+        from: &AccountId,
+        to: &AccountId,
+        asset_id: AssetId,
+        amount: Balance,
+    ) -> Result<(), StfError> {
+        let from_balance = self.balance(from, asset_id);
+        from_balance.free = from_balance
+            .free
+            .checked_sub(amount)
+            .ok_or(StfError::MissingFunds)?;
+        self.credit_free(to, asset_id, amount)
+    }
+
+    fn transfer_reserved(
+        &mut self,
+        from: &AccountId,
+        to: &AccountId,
+        asset_id: AssetId,
+        amount: Balance,
+    ) -> Result<(), StfError> {
+        let from_balance = self.balance(from, asset_id);
+        from_balance.reserved = from_balance
+            .reserved
+            .checked_sub(amount)
+            .ok_or(StfError::MissingFunds)?;
+        self.credit_free(to, asset_id, amount)
+    }
+
+    fn credit_free(
+        &mut self,
+        to: &AccountId,
+        asset_id: AssetId,
+        amount: Balance,
+    ) -> Result<(), StfError> {
+        let to_balance = self.balance(to, asset_id);
+        to_balance.free = to_balance
+            .free
+            .checked_add(amount)
+            .ok_or_else(|| StfError::Dispatch("order_book::overflow".to_string()))?;
+        Ok(())
+    }
+
+    fn apply(self) -> Result<(), StfError> {
+        for (who, asset_id, balance) in self.balances.into_iter() {
+            set_asset_balance(who, asset_id, balance)?;
+        }
+        Ok(())
+    }
+}
+
+fn ensure_free_balance(
+    who: &AccountId,
+    asset_id: AssetId,
+    amount: Balance,
+) -> Result<(), StfError> {
+    if get_asset_balance(who, asset_id).free < amount {
+        return Err(StfError::MissingFunds);
+    }
+    Ok(())
+}
+
+fn reserve(who: &AccountId, asset_id: AssetId, amount: Balance) -> Result<(), StfError> {
+    let mut balance = get_asset_balance(who, asset_id);
+    if balance.free < amount {
+        return Err(StfError::MissingFunds);
+    }
+    balance.free -= amount;
+    balance.reserved += amount;
+    set_asset_balance(who.clone(), asset_id, balance)
+}
+
+fn unreserve(who: &AccountId, asset_id: AssetId, amount: Balance) -> Result<(), StfError> {
+    let mut balance = get_asset_balance(who, asset_id);
+    if balance.reserved < amount {
+        return Err(StfError::MissingFunds);
+    }
+    balance.reserved -= amount;
+    balance.free += amount;
+    set_asset_balance(who.clone(), asset_id, balance)
+}
@@ -7,19 +7,23 @@ use codec::{Decode, Encode};
 use derive_more::Display;
 use log_sgx::*;
 use sgx_runtime::{Balance, BlockNumber as L1BlockNumer, Runtime};
+#[cfg(feature = "endow-test-accounts")]
 use sp_core::crypto::AccountId32;
+#[cfg(feature = "endow-test-accounts")]
 use sp_core::Pair;
 use sp_core::H256 as Hash;
 use sp_io::hashing::blake2_256;
 use sp_io::SgxExternalitiesTrait;
 use sp_runtime::MultiAddress;
 use substratee_worker_primitives::BlockNumber;
-use support::metadata::StorageHasher;
+pub use support::metadata::StorageHasher;
 use support::traits::UnfilteredDispatchable;
 
+use crate::{events, fees, governance, order_book};
 use crate::{
-    AccountId, Getter, Index, PublicGetter, ShardIdentifier, State, Stf, TrustedCall,
-    TrustedCallSigned, TrustedGetter, SUBSRATEE_REGISTRY_MODULE, UNSHIELD,
+    AccountId, AssetId, FeeSchedule, GenesisConfig, Getter, Index, MarketId, OrderId, PublicGetter,
+    ShardIdentifier, State, Stf, StfEvent, SudoCall, TrustedCall, TrustedCallSigned, TrustedGetter,
+    NATIVE_ASSET_ID, SUBSRATEE_REGISTRY_MODULE, UNSHIELD,
 };
 
 /// Simple blob that holds a call in encoded format
@@ -35,13 +39,26 @@ impl Encode for OpaqueCall {
 pub type AccountData = balances::AccountData<Balance>;
 pub type AccountInfo = system::AccountInfo<Index, AccountData>;
 
+/// Balance of an account in one asset
+#[derive(Encode, Decode, Default, Clone, Debug, PartialEq)]
+pub struct AssetBalance {
+    pub free: Balance,
+    pub reserved: Balance,
+}
+
+#[cfg(feature = "endow-test-accounts")]
 const ALICE_ENCODED: [u8; 32] = [
     212, 53, 147, 199, 21, 253, 211, 28, 97, 20, 26, 189, 4, 169, 159, 214, 130, 44, 133, 88, 133,
     76, 205, 227, 154, 86, 132, 231, 165, 109, 162, 125,
 ];
 
 impl Stf {
+    /// Initializes the state of a shard without genesis config
     pub fn init_state() -> State {
+        Self::init_state_from_genesis(&GenesisConfig::default())
+    }
+
+    pub fn init_state_from_genesis(genesis: &GenesisConfig) -> State {
         debug!("initializing stf state");
         let mut ext = State::new();
         // set initial state hash
@@ -49,28 +66,16 @@ impl Stf {
         ext.execute_with(|| {
             // do not set genesis for pallets that are meant to be on-chain
             // use get_storage_hashes_to_update instead
-            sp_io::storage::set(&storage_value_key("Sudo", "Key"), &ALICE_ENCODED);
+            if let Some(sudo) = &genesis.sudo {
+                sp_io::storage::set(&storage_value_key("Sudo", "Key"), &sudo.encode());
+            }
 
             sp_io::storage::set(
                 &storage_value_key("Balances", "TotalIssuance"),
                 &11u128.encode(),
             );
-            sp_io::storage::set(
-                &storage_value_key("Balances", "CreationFee"),
-                &1u128.encode(),
-            );
-            sp_io::storage::set(
-                &storage_value_key("Balances", "TransferFee"),
-                &1u128.encode(),
-            );
-            sp_io::storage::set(
-                &storage_value_key("Balances", "TransactionBaseFee"),
-                &1u128.encode(),
-            );
-            sp_io::storage::set(
-                &storage_value_key("Balances", "TransfactionByteFee"),
-                &1u128.encode(),
-            );
+            // fees are charged once root has set a schedule
+            fees::set_fee_schedule(&FeeSchedule::default());
             sp_io::storage::set(
                 &storage_value_key("Balances", "ExistentialDeposit"),
                 &1u128.encode(),
@@ -86,26 +91,35 @@ impl Stf {
                 &storage_value_key("System", "LastHash"),
                 &state_hash.encode(),
             );
-            //FIXME: for testing purpose only - maybe add feature?
-            // for example: feature = endowtestaccounts
-            let public = AccountId32::from(
-                sp_core::ed25519::Pair::from_seed(b"12345678901234567890123456789012").public(),
-            );
-            sgx_runtime::BalancesCall::<Runtime>::set_balance(
-                MultiAddress::Id(public.clone()),
-                2000,
-                2000,
-            )
-            .dispatch_bypass_filter(sgx_runtime::Origin::root())
-            .map_err(|_| StfError::Dispatch("balance_set_balance".to_string()))
-            .unwrap();
-
-            let print_public: [u8; 32] = public.clone().into();
-            if let Some(info) = get_account_info(&public) {
-                debug!("{:?} balance is {}", print_public, info.data.free);
-            } else {
-                debug!("{:?} balance is zero", print_public);
+            for asset_id in genesis.assets.iter() {
+                governance::dispatch(SudoCall::whitelist_asset(*asset_id))
+                    .expect("whitelisting an asset can not fail");
+            }
+            let native_balances = genesis
+                .balances
+                .iter()
+                .map(|(who, free)| (who, NATIVE_ASSET_ID, free));
+            let asset_balances = genesis
+                .asset_balances
+                .iter()
+                .map(|(who, asset_id, free)| (who, *asset_id, free));
+            for (who, asset_id, free) in native_balances.chain(asset_balances) {
+                let balance = AssetBalance {
+                    free: *free,
+                    reserved: 0,
+                };
+                if let Err(e) = set_asset_balance(who.clone(), asset_id, balance) {
+                    error!(
+                        "could not endow {:x?} with asset {}: {}",
+                        who.encode(),
+                        asset_id,
+                        e
+                    );
+                }
             }
+
+            #[cfg(feature = "endow-test-accounts")]
+            endow_test_accounts(genesis.sudo.is_none());
         });
         ext
     }
@@ -193,82 +207,268 @@ impl Stf {
         })
     }
 
+    /// Returns the encoded `RuntimeVersion` of the sgx runtime
+    pub fn get_runtime_version() -> Vec<u8> {
+        sgx_runtime::VERSION.encode()
+    }
+
+    /// Returns the encoded metadata of the sgx runtime
+    pub fn get_runtime_metadata() -> Vec<u8> {
+        Runtime::metadata().encode()
+    }
+
+    /// Executes the call and returns the events it emitted
     pub fn execute(
         ext: &mut State,
         call: TrustedCallSigned,
         calls: &mut Vec<OpaqueCall>,
-    ) -> Result<(), StfError> {
+    ) -> Result<Vec<StfEvent>, StfError> {
+        ensure_not_shield(&call.call)?;
         let call_hash = blake2_256(&call.encode());
-        ext.execute_with(|| {
-            let sender = call.call.account().clone();
+        let sender = call.call.account().clone();
+        let fee = ext.execute_with(|| {
             validate_nonce(&sender, call.nonce)?;
-            match call.call {
-                TrustedCall::balance_set_balance(root, who, free_balance, reserved_balance) => {
-                    Self::ensure_root(root)?;
-                    debug!(
-                        "balance_set_balance({:x?}, {}, {})",
-                        who.encode(),
-                        free_balance,
-                        reserved_balance
-                    );
-                    sgx_runtime::BalancesCall::<Runtime>::set_balance(
-                        MultiAddress::Id(who),
-                        free_balance,
-                        reserved_balance,
-                    )
-                    .dispatch_bypass_filter(sgx_runtime::Origin::root())
-                    .map_err(|_| StfError::Dispatch("balance_set_balance".to_string()))?;
-                    Ok(())
-                }
-                TrustedCall::balance_transfer(from, to, value) => {
-                    let origin = sgx_runtime::Origin::signed(from.clone());
-                    debug!(
-                        "balance_transfer({:x?}, {:x?}, {})",
-                        from.encode(),
-                        to.encode(),
-                        value
-                    );
-                    if let Some(info) = get_account_info(&from) {
-                        debug!("sender balance is {}", info.data.free);
-                    } else {
-                        debug!("sender balance is zero");
-                    }
-                    sgx_runtime::BalancesCall::<Runtime>::transfer(MultiAddress::Id(to), value)
-                        .dispatch_bypass_filter(origin)
-                        .map_err(|_| StfError::Dispatch("balance_transfer".to_string()))?;
-                    Ok(())
+            governance::ensure_not_paused(&call.call)?;
+            fees::charge_call_fee(&sender, &call.call)
+        })?;
+        // a failing call still pays its fee and consumes its nonce, so that it can not be replayed
+        let result = Self::dispatch_or_roll_back(ext, call.call, call_hash, calls);
+        ext.execute_with(|| {
+            increment_nonce(&sender);
+            let mut events = result?;
+            if fee > 0 {
+                events.insert(0, StfEvent::CallFeePaid(sender.clone(), fee));
+            }
+            events::deposit_call_events(call_hash.into(), events.clone());
+            Ok(events)
+        })
+    }
+
+    /// Executes a shield of funds deposited by the on-chain extrinsic with `xt_hash`
+    ///
+    /// The shield is authorized by the extrinsic rather than signed by the account,
+    /// so it neither requires nor consumes a nonce of the account.
+    pub fn execute_shield(
+        ext: &mut State,
+        call: TrustedCall,
+        xt_hash: [u8; 32],
+    ) -> Result<Vec<StfEvent>, StfError> {
+        match call {
+            TrustedCall::balance_shield(_, _) | TrustedCall::asset_shield(_, _, _) => (),
+            _ => return Err(StfError::Dispatch("execute_shield".to_string())),
+        }
+        let events = Self::dispatch_or_roll_back(ext, call, xt_hash, &mut Vec::new())?;
+        ext.execute_with(|| events::deposit_call_events(xt_hash.into(), events.clone()));
+        Ok(events)
+    }
+
+    // a failing call must not leave any of its effects applied
+    fn dispatch_or_roll_back(
+        ext: &mut State,
+        call: TrustedCall,
+        call_hash: [u8; 32],
+        calls: &mut Vec<OpaqueCall>,
+    ) -> Result<Vec<StfEvent>, StfError> {
+        let snapshot = (ext.clone(), calls.len());
+        let result = ext.execute_with(|| Self::dispatch(call, call_hash, calls));
+        if let Err(e) = &result {
+            debug!("call failed: {}, rolling back its effects", e);
+            let (state, calls_len) = snapshot;
+            *ext = state;
+            calls.truncate(calls_len);
+        }
+        result
+    }
+
+    fn dispatch(
+        call: TrustedCall,
+        call_hash: [u8; 32],
+        calls: &mut Vec<OpaqueCall>,
+    ) -> Result<Vec<StfEvent>, StfError> {
+        match call {
+            TrustedCall::balance_set_balance(root, who, free_balance, reserved_balance) => {
+                Self::ensure_root(root)?;
+                debug!(
+                    "balance_set_balance({:x?}, {}, {})",
+                    who.encode(),
+                    free_balance,
+                    reserved_balance
+                );
+                sgx_runtime::BalancesCall::<Runtime>::set_balance(
+                    MultiAddress::Id(who.clone()),
+                    free_balance,
+                    reserved_balance,
+                )
+                .dispatch_bypass_filter(sgx_runtime::Origin::root())
+                .map_err(|_| StfError::Dispatch("balance_set_balance".to_string()))?;
+                Ok(vec![StfEvent::BalanceSet(
+                    who,
+                    free_balance,
+                    reserved_balance,
+                )])
+            }
+            TrustedCall::balance_transfer(from, to, value) => {
+                let origin = sgx_runtime::Origin::signed(from.clone());
+                debug!(
+                    "balance_transfer({:x?}, {:x?}, {})",
+                    from.encode(),
+                    to.encode(),
+                    value
+                );
+                if let Some(info) = get_account_info(&from) {
+                    debug!("sender balance is {}", info.data.free);
+                } else {
+                    debug!("sender balance is zero");
                 }
-                TrustedCall::balance_unshield(account_incognito, beneficiary, value, shard) => {
-                    debug!(
-                        "balance_unshield({:x?}, {:x?}, {}, {})",
-                        account_incognito.encode(),
-                        beneficiary.encode(),
+                sgx_runtime::BalancesCall::<Runtime>::transfer(MultiAddress::Id(to.clone()), value)
+                    .dispatch_bypass_filter(origin)
+                    .map_err(|_| StfError::Dispatch("balance_transfer".to_string()))?;
+                Ok(vec![StfEvent::Transferred(
+                    from,
+                    to,
+                    NATIVE_ASSET_ID,
+                    value,
+                )])
+            }
+            TrustedCall::balance_unshield(account_incognito, beneficiary, value, shard) => {
+                debug!(
+                    "balance_unshield({:x?}, {:x?}, {}, {})",
+                    account_incognito.encode(),
+                    beneficiary.encode(),
+                    value,
+                    shard
+                );
+
+                Self::unshield_funds(account_incognito.clone(), value)?;
+                calls.push(OpaqueCall(
+                    (
+                        [SUBSRATEE_REGISTRY_MODULE, UNSHIELD],
+                        beneficiary.clone(),
+                        NATIVE_ASSET_ID,
                         value,
-                        shard
-                    );
-
-                    Self::unshield_funds(account_incognito, value)?;
-                    calls.push(OpaqueCall(
-                        (
-                            [SUBSRATEE_REGISTRY_MODULE, UNSHIELD],
-                            beneficiary,
-                            value,
-                            shard,
-                            call_hash,
-                        )
-                            .encode(),
-                    ));
-                    Ok(())
+                        shard,
+                        call_hash,
+                    )
+                        .encode(),
+                ));
+                Ok(vec![StfEvent::Unshielded(
+                    account_incognito,
+                    beneficiary,
+                    NATIVE_ASSET_ID,
+                    value,
+                )])
+            }
+            TrustedCall::balance_shield(who, value) => {
+                debug!("balance_shield({:x?}, {})", who.encode(), value);
+                Self::shield_funds(who.clone(), value)?;
+                Ok(vec![StfEvent::Shielded(who, NATIVE_ASSET_ID, value)])
+            }
+            TrustedCall::asset_transfer(from, to, asset_id, value) => {
+                debug!(
+                    "asset_transfer({:x?}, {:x?}, {}, {})",
+                    from.encode(),
+                    to.encode(),
+                    asset_id,
+                    value
+                );
+                Self::transfer_asset(from.clone(), to.clone(), asset_id, value)?;
+                Ok(vec![StfEvent::Transferred(from, to, asset_id, value)])
+            }
+            TrustedCall::asset_unshield(account_incognito, beneficiary, asset_id, value, shard) => {
+                debug!(
+                    "asset_unshield({:x?}, {:x?}, {}, {}, {})",
+                    account_incognito.encode(),
+                    beneficiary.encode(),
+                    asset_id,
+                    value,
+                    shard
+                );
+
+                Self::unshield_asset(account_incognito.clone(), asset_id, value)?;
+                calls.push(OpaqueCall(
+                    (
+                        [SUBSRATEE_REGISTRY_MODULE, UNSHIELD],
+                        beneficiary.clone(),
+                        asset_id,
+                        value,
+                        shard,
+                        call_hash,
+                    )
+                        .encode(),
+                ));
+                Ok(vec![StfEvent::Unshielded(
+                    account_incognito,
+                    beneficiary,
+                    asset_id,
+                    value,
+                )])
+            }
+            TrustedCall::asset_shield(who, asset_id, value) => {
+                debug!("asset_shield({:x?}, {}, {})", who.encode(), asset_id, value);
+                if !governance::is_asset_whitelisted(asset_id) {
+                    return Err(StfError::AssetNotWhitelisted(asset_id));
                 }
-                TrustedCall::balance_shield(who, value) => {
-                    debug!("balance_shield({:x?}, {})", who.encode(), value);
-                    Self::shield_funds(who, value)?;
-                    Ok(())
+                Self::shield_asset(who.clone(), asset_id, value)?;
+                Ok(vec![StfEvent::Shielded(who, asset_id, value)])
+            }
+            TrustedCall::place_order(who, market, side, order_type, price, quantity) => {
+                debug!(
+                    "place_order({:x?}, {:?}, {:?}, {:?}, {}, {})",
+                    who.encode(),
+                    market,
+                    side,
+                    order_type,
+                    price,
+                    quantity
+                );
+                let (order_id, trades) = order_book::place_order(
+                    who.clone(),
+                    market,
+                    side,
+                    order_type,
+                    price,
+                    quantity,
+                )?;
+                let mut events = vec![StfEvent::OrderPlaced(who, market, order_id)];
+                events.extend(trades.into_iter().map(StfEvent::OrderFilled));
+                Ok(events)
+            }
+            TrustedCall::cancel_order(who, market, order_id) => {
+                debug!(
+                    "cancel_order({:x?}, {:?}, {})",
+                    who.encode(),
+                    market,
+                    order_id
+                );
+                order_book::cancel_order(&who, &market, order_id)?;
+                Ok(vec![StfEvent::OrderCancelled(who, market, order_id)])
+            }
+            TrustedCall::sudo(root, sudo_call, shard) => {
+                Self::ensure_root(root)?;
+                debug!("sudo({:?}, {})", sudo_call, shard);
+                // the enclave confirms the call on chain once the resulting state is written
+                governance::dispatch(sudo_call.clone())?;
+                Ok(vec![StfEvent::SudoExecuted(sudo_call)])
+            }
+            TrustedCall::batch(sender, batch_calls) => {
+                debug!("batch({:x?}, {} calls)", sender.encode(), batch_calls.len());
+                let mut events = Vec::new();
+                for batch_call in batch_calls {
+                    if *batch_call.account() != sender {
+                        return Err(StfError::InvalidBatch(
+                            "calls must be sent by the sender of the batch".to_string(),
+                        ));
+                    }
+                    if let TrustedCall::batch(_, _) = batch_call {
+                        return Err(StfError::InvalidBatch(
+                            "batches can not be nested".to_string(),
+                        ));
+                    }
+                    events.extend(Self::dispatch(batch_call, call_hash, calls)?);
                 }
-            }?;
-            increment_nonce(&sender);
-            Ok(())
-        })
+                Ok(events)
+            }
+        }
     }
 
     pub fn account_nonce(ext: &mut State, account: &AccountId) -> Index {
@@ -324,19 +524,74 @@ impl Stf {
                         None
                     }
                 }
+                TrustedGetter::asset_free_balance(who, asset_id) => {
+                    let balance = get_asset_balance(&who, asset_id);
+                    debug!(
+                        "Account free balance of asset {} is {}",
+                        asset_id, balance.free
+                    );
+                    Some(balance.free.encode())
+                }
+                TrustedGetter::asset_reserved_balance(who, asset_id) => {
+                    let balance = get_asset_balance(&who, asset_id);
+                    debug!(
+                        "Account reserved balance of asset {} is {}",
+                        asset_id, balance.reserved
+                    );
+                    Some(balance.reserved.encode())
+                }
+                TrustedGetter::open_orders(who) => Some(order_book::get_open_orders(&who).encode()),
+                TrustedGetter::order(who, id) => {
+                    order_book::get_order(&who, id).map(|order| order.encode())
+                }
+                TrustedGetter::trade_history(who) => {
+                    Some(order_book::get_trade_history(&who).encode())
+                }
+                TrustedGetter::order_reserved_balance(who, asset_id) => {
+                    Some(order_book::get_order_reserved_balance(&who, asset_id).encode())
+                }
+                // only the fee recipient and root may see how much has been collected
+                TrustedGetter::accrued_fees(who, asset_id) => {
+                    if Some(who.clone()) == fees::get_fee_recipient()
+                        || Self::ensure_root(who).is_ok()
+                    {
+                        Some(fees::get_accrued_fees(asset_id).encode())
+                    } else {
+                        None
+                    }
+                }
+                TrustedGetter::events(who, block_number) => {
+                    let block_events = events::get_block_events(block_number);
+                    if Self::ensure_root(who.clone()).is_ok() {
+                        return Some(block_events.encode());
+                    }
+                    let own_events: Vec<(Hash, Vec<StfEvent>)> = block_events
+                        .into_iter()
+                        .map(|(call_hash, call_events)| {
+                            let call_events = call_events
+                                .into_iter()
+                                .filter(|event| event.involves(&who))
+                                .collect::<Vec<_>>();
+                            (call_hash, call_events)
+                        })
+                        .filter(|(_, call_events)| !call_events.is_empty())
+                        .collect();
+                    Some(own_events.encode())
+                }
             },
             Getter::public(g) => match g {
-                PublicGetter::some_value => Some(42u32.encode()),
+                PublicGetter::market_depth(market) => {
+                    Some(order_book::get_market_depth(&market).encode())
+                }
+                PublicGetter::last_price(market) => {
+                    order_book::get_last_price(&market).map(|price| price.encode())
+                }
             },
         })
     }
 
     fn ensure_root(account: AccountId) -> Result<(), StfError> {
-        if sp_io::storage::get(&storage_value_key("Sudo", "Key")).unwrap() == account.encode() {
-            Ok(())
-        } else {
-            Err(StfError::MissingPrivileges(account))
-        }
+        governance::ensure_root(account)
     }
 
     fn shield_funds(account: AccountId, amount: u128) -> Result<(), StfError> {
@@ -379,6 +634,55 @@ impl Stf {
         }
     }
 
+    fn shield_asset(
+        account: AccountId,
+        asset_id: AssetId,
+        amount: Balance,
+    ) -> Result<(), StfError> {
+        let mut balance = get_asset_balance(&account, asset_id);
+        balance.free = balance
+            .free
+            .checked_add(amount)
+            .ok_or_else(|| StfError::Dispatch("shield_asset::overflow".to_string()))?;
+        set_asset_balance(account, asset_id, balance)
+    }
+
+    fn unshield_asset(
+        account: AccountId,
+        asset_id: AssetId,
+        amount: Balance,
+    ) -> Result<(), StfError> {
+        let mut balance = get_asset_balance(&account, asset_id);
+        if balance.free < amount {
+            return Err(StfError::MissingFunds);
+        }
+        balance.free -= amount;
+        set_asset_balance(account, asset_id, balance)
+    }
+
+    fn transfer_asset(
+        from: AccountId,
+        to: AccountId,
+        asset_id: AssetId,
+        amount: Balance,
+    ) -> Result<(), StfError> {
+        let mut from_balance = get_asset_balance(&from, asset_id);
+        if from_balance.free < amount {
+            return Err(StfError::MissingFunds);
+        }
+        if from == to {
+            return Ok(());
+        }
+        let mut to_balance = get_asset_balance(&to, asset_id);
+        from_balance.free -= amount;
+        to_balance.free = to_balance
+            .free
+            .checked_add(amount)
+            .ok_or_else(|| StfError::Dispatch("transfer_asset::overflow".to_string()))?;
+        set_asset_balance(from, asset_id, from_balance)?;
+        set_asset_balance(to, asset_id, to_balance)
+    }
+
     pub fn get_storage_hashes_to_update(call: &TrustedCallSigned) -> Vec<Vec<u8>> {
         let key_hashes = Vec::new();
         match call.call {
@@ -386,6 +690,13 @@ impl Stf {
             TrustedCall::balance_transfer(_, _, _) => debug!("No storage updates needed..."),
             TrustedCall::balance_unshield(_, _, _, _) => debug!("No storage updates needed..."),
             TrustedCall::balance_shield(_, _) => debug!("No storage updates needed..."),
+            TrustedCall::asset_transfer(_, _, _, _) => debug!("No storage updates needed..."),
+            TrustedCall::asset_unshield(_, _, _, _, _) => debug!("No storage updates needed..."),
+            TrustedCall::asset_shield(_, _, _) => debug!("No storage updates needed..."),
+            TrustedCall::place_order(_, _, _, _, _, _) => debug!("No storage updates needed..."),
+            TrustedCall::cancel_order(_, _, _) => debug!("No storage updates needed..."),
+            TrustedCall::sudo(_, _, _) => debug!("No storage updates needed..."),
+            TrustedCall::batch(_, _) => debug!("No storage updates needed..."),
         };
         key_hashes
     }
@@ -417,6 +728,30 @@ pub fn shards_key_hash() -> Vec<u8> {
     vec![]
 }
 
+/// Funds a well-known test account and makes Alice Sudo, if there is no Sudo key yet
+///
+/// Never enable this for shards holding real funds.
+#[cfg(feature = "endow-test-accounts")]
+fn endow_test_accounts(set_sudo: bool) {
+    if set_sudo {
+        sp_io::storage::set(&storage_value_key("Sudo", "Key"), &ALICE_ENCODED);
+    }
+    let public = AccountId32::from(
+        sp_core::ed25519::Pair::from_seed(b"12345678901234567890123456789012").public(),
+    );
+    sgx_runtime::BalancesCall::<Runtime>::set_balance(MultiAddress::Id(public.clone()), 2000, 2000)
+        .dispatch_bypass_filter(sgx_runtime::Origin::root())
+        .map_err(|_| StfError::Dispatch("balance_set_balance".to_string()))
+        .unwrap();
+
+    let print_public: [u8; 32] = public.clone().into();
+    if let Some(info) = get_account_info(&public) {
+        debug!("{:?} balance is {}", print_public, info.data.free);
+    } else {
+        debug!("{:?} balance is zero", print_public);
+    }
+}
+
 // get the AccountInfo key where the account is stored
 pub fn account_key_hash(account: &AccountId) -> Vec<u8> {
     storage_map_key(
@@ -444,6 +779,66 @@ fn get_account_info(who: &AccountId) -> Option<AccountInfo> {
     }
 }
 
+// get the key under which the balance of an account in a non-native asset is stored
+pub fn asset_balance_key_hash(account: &AccountId, asset_id: AssetId) -> Vec<u8> {
+    storage_double_map_key(
+        "Assets",
+        "Account",
+        &asset_id,
+        &StorageHasher::Blake2_128Concat,
+        account,
+        &StorageHasher::Blake2_128Concat,
+    )
+}
+
+/// the native asset is held by the balances pallet, all others in the assets storage
+pub(crate) fn get_asset_balance(who: &AccountId, asset_id: AssetId) -> AssetBalance {
+    if asset_id == NATIVE_ASSET_ID {
+        return get_account_info(who).map_or_else(Default::default, |info| AssetBalance {
+            free: info.data.free,
+            reserved: info.data.reserved,
+        });
+    }
+    sp_io::storage::get(&asset_balance_key_hash(who, asset_id))
+        .and_then(|balance| AssetBalance::decode(&mut balance.as_slice()).ok())
+        .unwrap_or_default()
+}
+
+pub(crate) fn set_asset_balance(
+    who: AccountId,
+    asset_id: AssetId,
+    balance: AssetBalance,
+) -> Result<(), StfError> {
+    if asset_id == NATIVE_ASSET_ID {
+        sgx_runtime::BalancesCall::<Runtime>::set_balance(
+            MultiAddress::Id(who),
+            balance.free,
+            balance.reserved,
+        )
+        .dispatch_bypass_filter(sgx_runtime::Origin::root())
+        .map_err(|_| StfError::Dispatch("set_asset_balance".to_string()))?;
+        return Ok(());
+    }
+    let key = asset_balance_key_hash(&who, asset_id);
+    if balance == AssetBalance::default() {
+        sp_io::storage::clear(&key);
+    } else {
+        sp_io::storage::set(&key, &balance.encode());
+    }
+    Ok(())
+}
+
+/// shields are authorized by their deposit on chain, an account can not sign them
+fn ensure_not_shield(call: &TrustedCall) -> Result<(), StfError> {
+    match call {
+        TrustedCall::balance_shield(_, _) | TrustedCall::asset_shield(_, _, _) => {
+            Err(StfError::UnauthorizedShield)
+        }
+        TrustedCall::batch(_, calls) => calls.iter().try_for_each(ensure_not_shield),
+        _ => Ok(()),
+    }
+}
+
 fn validate_nonce(who: &AccountId, nonce: Index) -> Result<(), StfError> {
     // validate
     let expected_nonce = get_account_info(who).map_or_else(|| 0, |acc| acc.nonce);
@@ -453,22 +848,20 @@ fn validate_nonce(who: &AccountId, nonce: Index) -> Result<(), StfError> {
     Err(StfError::InvalidNonce(nonce))
 }
 
-/// increment nonce after a successful call execution
+/// increment nonce after a call execution, whether it succeeded or not
+///
+/// Accounts which only hold non-native assets have no account info yet, it is created here
+/// so that their calls can not be replayed.
 fn increment_nonce(account: &AccountId) {
-    //FIXME: Proper error handling - should be taken into
-    // consideration after implementing pay fee check
-    if let Some(mut acc_info) = get_account_info(account) {
-        debug!("incrementing account nonce");
-        acc_info.nonce += 1;
-        sp_io::storage::set(&account_key_hash(account), &acc_info.encode());
-        debug!(
-            "updated account {:?} nonce: {:?}",
-            account.encode(),
-            get_account_info(account).unwrap().nonce
-        );
-    } else {
-        error!("tried to increment nonce of a non-existent account")
-    }
+    let mut acc_info = get_account_info(account).unwrap_or_default();
+    debug!("incrementing account nonce");
+    acc_info.nonce += 1;
+    sp_io::storage::set(&account_key_hash(account), &acc_info.encode());
+    debug!(
+        "updated account {:?} nonce: {:?}",
+        account.encode(),
+        acc_info.nonce
+    );
 }
 
 pub fn storage_value_key(module_prefix: &str, storage_prefix: &str) -> Vec<u8> {
@@ -530,7 +923,7 @@ fn key_hash<K: Encode>(key: &K, hasher: &StorageHasher) -> Vec<u8> {
 pub enum StfError {
     #[display(fmt = "Insufficient privileges {:?}, are you sure you are root?", _0)]
     MissingPrivileges(AccountId),
-    #[display(fmt = "Error dispatching runtime call")]
+    #[display(fmt = "Error dispatching runtime call: {}", _0)]
     Dispatch(String),
     #[display(fmt = "Not enough funds to perform operation")]
     MissingFunds,
@@ -538,4 +931,20 @@ pub enum StfError {
     InexistentAccount(AccountId),
     #[display(fmt = "Invalid Nonce {:?}", _0)]
     InvalidNonce(Index),
+    #[display(fmt = "Invalid order: {}", _0)]
+    InvalidOrder(String),
+    #[display(fmt = "Order {} does not exist", _0)]
+    InexistentOrder(OrderId),
+    #[display(fmt = "Not enough funds to pay the fee of {}", _0)]
+    MissingFeeFunds(Balance),
+    #[display(fmt = "Market {:?} is not registered or can not be registered", _0)]
+    InvalidMarket(MarketId),
+    #[display(fmt = "Shard is paused")]
+    ShardPaused,
+    #[display(fmt = "Invalid batch: {}", _0)]
+    InvalidBatch(String),
+    #[display(fmt = "Asset {} is not whitelisted", _0)]
+    AssetNotWhitelisted(AssetId),
+    #[display(fmt = "Shielding is only executed for deposits on chain")]
+    UnauthorizedShield,
 }
\ No newline at end of file
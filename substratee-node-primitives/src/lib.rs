@@ -11,6 +11,7 @@ use sp_core::H256;
 
 pub type ShardIdentifier = H256;
 pub type BlockNumber = u32;
+pub type AssetId = u32;
 
 // Note in the substratee-pallet-registry this is a struct. But for the coded this does not matter.
 #[derive(Encode, Decode, Default, Clone, PartialEq, Eq, Debug)]
@@ -20,7 +21,7 @@ pub struct Request {
 }
 
 pub type SubstrateeConfirmCallFn = ([u8; 2], ShardIdentifier, H256, Vec<u8>);
-pub type ShieldFundsFn = ([u8; 2], Vec<u8>, u128, ShardIdentifier);
+pub type ShieldFundsFn = ([u8; 2], Vec<u8>, AssetId, u128, ShardIdentifier);
 pub type CallWorkerFn = ([u8; 2], Request);
 
 #[cfg(feature = "std")]
@@ -1,4 +1,4 @@
-use crate::{BlockNumber, ShardIdentifier};
+use crate::{BlockHash, BlockNumber, ShardIdentifier};
 use codec::{Decode, Encode};
 #[cfg(feature = "std")]
 use serde::{Deserialize, Serialize};
@@ -11,7 +11,7 @@ use std::vec::Vec;
 // but block.rs should be available in std?
 //use sgx_runtime::BlockNumber;
 use sp_core::crypto::{AccountId32, Pair};
-use sp_core::{ed25519, H256};
+use sp_core::{blake2_256, ed25519, H256};
 use sp_runtime::{traits::Verify, MultiSignature};
 
 pub type Signature = MultiSignature;
@@ -81,6 +81,27 @@ pub struct Block {
     state_payload: Vec<u8>,
 }
 
+/// identifies a block within a shard
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub enum BlockId {
+    Hash(BlockHash),
+    Number(BlockNumber),
+}
+
+/// header of a block as pushed to the subscribers of the sidechain heads
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Debug)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Header {
+    pub block_number: BlockNumber,
+    pub parent_hash: H256,
+    /// hash of the block this header belongs to
+    pub block_hash: H256,
+    pub layer_one_head: H256,
+    pub shard_id: ShardIdentifier,
+    pub signed_top_hashes: Vec<H256>,
+}
+
 impl Block {
     ///get block number
     pub fn block_number(&self) -> u64 {
@@ -114,6 +135,21 @@ impl Block {
     pub fn state_payload(&self) -> &Vec<u8> {
         &self.state_payload
     }
+    /// get blake2_256 hash of the encoded block
+    pub fn hash(&self) -> H256 {
+        blake2_256(&self.encode()).into()
+    }
+    /// get header of block
+    pub fn header(&self) -> Header {
+        Header {
+            block_number: self.block_number,
+            parent_hash: self.parent_hash,
+            block_hash: self.hash(),
+            layer_one_head: self.layer_one_head,
+            shard_id: self.shard_id,
+            signed_top_hashes: self.signed_top_hashes.clone(),
+        }
+    }
     /// Constructs an unsigned block
     pub fn construct_block(
         author: AccountId32,
@@ -327,6 +363,56 @@ mod tests {
         assert_eq!(signed_block.verify_signature(), false);
     }
 
+    #[test]
+    fn block_hash_works() {
+        // given
+        let signer_pair = ed25519::Pair::from_string("//Alice", None).unwrap();
+        let author: AccountId32 = signer_pair.public().into();
+        let block = Block::construct_block(
+            author,
+            0,
+            H256::random(),
+            H256::random(),
+            ShardIdentifier::default(),
+            vec![],
+            vec![],
+        );
+
+        // when
+        let hash = block.hash();
+
+        // then
+        assert_eq!(hash, H256::from(blake2_256(&block.encode())));
+    }
+
+    #[test]
+    fn block_header_works() {
+        // given
+        let signer_pair = ed25519::Pair::from_string("//Alice", None).unwrap();
+        let author: AccountId32 = signer_pair.public().into();
+        let signed_top_hashes = vec![H256::random()];
+        let block = Block::construct_block(
+            author,
+            1,
+            H256::random(),
+            H256::random(),
+            ShardIdentifier::random(),
+            signed_top_hashes.clone(),
+            vec![],
+        );
+
+        // when
+        let header = block.header();
+
+        // then
+        assert_eq!(header.block_number, block.block_number());
+        assert_eq!(header.parent_hash, block.parent_hash());
+        assert_eq!(header.block_hash, block.hash());
+        assert_eq!(header.layer_one_head, block.layer_one_head());
+        assert_eq!(header.shard_id, block.shard_id());
+        assert_eq!(header.signed_top_hashes, signed_top_hashes);
+    }
+
     #[test]
     fn get_time_works() {
         // given
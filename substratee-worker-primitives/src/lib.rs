@@ -3,19 +3,25 @@
 
 pub mod block;
 
+use aes::Aes128;
 use codec::{Decode, Encode};
+use ofb::stream_cipher::{NewStreamCipher, SyncStreamCipher};
+use ofb::Ofb;
 #[cfg(feature = "std")]
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "sgx")]
 use sgx_tstd as std;
-use sp_core::H256;
+use sp_core::crypto::{AccountId32, Pair};
+use sp_core::{blake2_256, ed25519, H256};
+use sp_runtime::{traits::Verify, MultiSignature};
+use std::string::String;
 use std::vec::Vec;
 
 pub type BlockHash = H256;
 pub type BlockNumber = u64;
 pub type ShardIdentifier = H256;
-
-//use sp_core::ed25519::Signature;
+/// Identifies a chain followed by the chain relay of the enclave
+pub type RelayId = u64;
 
 #[derive(Debug, Clone, PartialEq, Encode, Decode)]
 pub enum DirectRequestStatus {
@@ -53,26 +59,176 @@ pub enum TrustedOperationStatus {
     Dropped,
     /// TrustedOperation is no longer valid in the current state.
     Invalid,
+    /// TrustedOperation has been rejected by the STF or for its signature, with the reason why.
+    ExecutionFailed(String),
+    /// TrustedOperation has been executed, the return value holds the encoded events it emitted.
+    Executed,
 }
 
-#[derive(Encode, Decode)]
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
 pub struct RpcReturnValue {
     pub value: Vec<u8>,
     pub do_watch: bool,
     pub status: DirectRequestStatus,
-    //pub signature: Signature,
+    /// hash of the request the value answers, see [`request_hash`]
+    ///
+    /// Status updates of a watched trusted operation carry the hash of the operation instead.
+    pub request_hash: H256,
+    /// shard the request was made for, the default shard if it does not concern one
+    pub shard: ShardIdentifier,
+    /// signature of the enclave over all of the above
+    ///
+    /// `None` if the return value has been composed outside of the enclave
+    pub signature: Option<MultiSignature>,
 }
 impl RpcReturnValue {
+    /// Composes an unsigned return value, which does not answer a particular request
     pub fn new(val: Vec<u8>, watch: bool, status: DirectRequestStatus) -> Self {
         Self {
             value: val,
             do_watch: watch,
             status,
-            //signature: sign,
+            request_hash: Default::default(),
+            shard: Default::default(),
+            signature: None,
+        }
+    }
+
+    /// Binds the return value to the request it answers
+    pub fn for_request(mut self, request_hash: H256, shard: ShardIdentifier) -> Self {
+        self.request_hash = request_hash;
+        self.shard = shard;
+        self
+    }
+
+    /// Signs the return value with the enclave signing key
+    pub fn sign(mut self, pair: &ed25519::Pair) -> Self {
+        self.signature = Some(pair.sign(self.signed_payload().as_slice()).into());
+        self
+    }
+
+    /// Whether the return value has been composed by the untrusted worker outside of the enclave
+    ///
+    /// Nothing in it is vouched for by the enclave, so a client has to accept it explicitly.
+    pub fn is_untrusted(&self) -> bool {
+        self.signature.is_none()
+    }
+
+    /// Verifies that the return value has been signed by `signer`
+    pub fn verify_signature(&self, signer: &AccountId32) -> bool {
+        match &self.signature {
+            Some(signature) => signature.verify(self.signed_payload().as_slice(), signer),
+            None => false,
+        }
+    }
+
+    /// Whether the return value answers the request with the given hash made for `shard`
+    ///
+    /// Only meaningful once the signature has been verified, it prevents the signed answer of
+    /// one request from being replayed as the answer of another one.
+    pub fn answers(&self, request_hash: &H256, shard: &ShardIdentifier) -> bool {
+        self.request_hash == *request_hash && self.shard == *shard
+    }
+
+    fn signed_payload(&self) -> Vec<u8> {
+        (
+            &self.value,
+            self.do_watch,
+            &self.status,
+            &self.request_hash,
+            &self.shard,
+        )
+            .encode()
+    }
+}
+
+/// Hash of a direct rpc request, over its method and encoded params
+pub fn request_hash(method: &str, params: &[u8]) -> H256 {
+    blake2_256(&(method, params).encode()).into()
+}
+
+type AesOfb = Ofb<Aes128>;
+
+/// Key with which the enclave encrypts the result of a trusted getter
+///
+/// A client appends it to the trusted operation before shielding it with the enclave's
+/// shielding key, so that only the client can read the result.
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode)]
+pub struct GetterResponseKey {
+    pub key: [u8; 16],
+    pub iv: [u8; 16],
+}
+
+impl GetterResponseKey {
+    pub fn new(key: [u8; 16], iv: [u8; 16]) -> Self {
+        Self { key, iv }
+    }
+
+    /// If AES acts on the encrypted data it decrypts and vice versa
+    pub fn de_or_encrypt(&self, bytes: &mut [u8]) {
+        // key and iv have the block size of AES-128, so initialization can not fail
+        let mut ofb = AesOfb::new_var(&self.key, &self.iv).expect("valid key and iv length");
+        ofb.apply_keystream(bytes);
+    }
+}
+
+/// Block production settings of a worker, passed into the enclave at init
+///
+/// All durations are in milliseconds.
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(default))]
+pub struct BlockProductionConfig {
+    /// Interval between the production of two sidechain blocks
+    pub block_time: u64,
+    /// Time budget for executing trusted calls per block
+    pub call_timeout: u64,
+    /// Maximum number of trusted operations executed per block and shard
+    pub max_operations_per_block: u32,
+    /// Number of the latest finalized layer one headers and checkpoints kept by the chain relay
+    pub relay_header_retention: u32,
+    /// Number of finalized layer one blocks within which an extrinsic sent by the enclave
+    /// must be included, before it is sent again. Extrinsics are only valid for this many
+    /// blocks, so it is rounded up to a power of two between 4 and 4096.
+    pub xt_inclusion_timeout: u32,
+}
+
+impl Default for BlockProductionConfig {
+    fn default() -> Self {
+        Self {
+            block_time: 1000,
+            call_timeout: 300,
+            max_operations_per_block: 1000,
+            relay_header_retention: 1000,
+            xt_inclusion_timeout: 20,
         }
     }
 }
 
+/// Health of a worker as reported by the `system_health` rpc method
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct SystemHealth {
+    /// Number of the latest sidechain block of the requested shard
+    pub sidechain_block_number: Option<BlockNumber>,
+    /// Number of operations in the ready queue of the requested shard
+    pub pool_ready: u64,
+    /// Number of operations in the future queue of the requested shard
+    pub pool_future: u64,
+    /// Number of the latest layer one block finalized by the chain relay
+    pub relay_finalized_block_number: Option<u32>,
+    /// Number of the latest layer one block the shard state has been synced to
+    pub state_layer_one_block_number: Option<u32>,
+    /// Number of extrinsics sent to layer one which were not included in time and sent again
+    pub stuck_extrinsics: Option<u64>,
+}
+
+/// Versions reported by the `system_version` rpc method
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+pub struct SystemVersion {
+    pub enclave: String,
+    pub stf: String,
+}
+
 #[cfg(feature = "std")]
 #[derive(Encode, Decode, Serialize, Deserialize)]
 pub struct RpcResponse {
@@ -82,7 +238,7 @@ pub struct RpcResponse {
 }
 
 #[cfg(feature = "std")]
-#[derive(Encode, Decode, Serialize)]
+#[derive(Encode, Decode, Serialize, Deserialize)]
 pub struct RpcRequest {
     pub jsonrpc: String,
     pub method: String,
@@ -102,3 +258,82 @@ impl RpcRequest {
         serde_json::to_string(&direct_invocation_call).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_return_value() -> RpcReturnValue {
+        RpcReturnValue::new(
+            vec![1, 2, 3],
+            true,
+            DirectRequestStatus::TrustedOperationStatus(TrustedOperationStatus::Submitted),
+        )
+    }
+
+    #[test]
+    fn getter_response_key_encryption_works() {
+        let response_key = GetterResponseKey::new([1u8; 16], [2u8; 16]);
+        let plaintext = Some(vec![42u8; 40]).encode();
+
+        let mut bytes = plaintext.clone();
+        response_key.de_or_encrypt(&mut bytes);
+        assert_ne!(bytes, plaintext);
+
+        response_key.de_or_encrypt(&mut bytes);
+        assert_eq!(bytes, plaintext);
+    }
+
+    #[test]
+    fn signing_return_value_works() {
+        let signer_pair = ed25519::Pair::from_string("//Alice", None).unwrap();
+        let signer: AccountId32 = signer_pair.public().into();
+
+        let return_value = test_return_value().sign(&signer_pair);
+        let decoded = RpcReturnValue::decode(&mut return_value.encode().as_slice()).unwrap();
+
+        assert!(decoded.verify_signature(&signer));
+        assert!(!decoded.is_untrusted());
+        assert!(test_return_value().is_untrusted());
+    }
+
+    #[test]
+    fn return_value_answers_only_its_request() {
+        let signer_pair = ed25519::Pair::from_string("//Alice", None).unwrap();
+        let signer: AccountId32 = signer_pair.public().into();
+        let request = request_hash("chain_getBlock", &[1, 2, 3]);
+        let shard = ShardIdentifier::from([1u8; 32]);
+
+        let return_value = test_return_value()
+            .for_request(request, shard)
+            .sign(&signer_pair);
+
+        assert!(return_value.answers(&request, &shard));
+        assert!(!return_value.answers(&request_hash("chain_getHeader", &[1, 2, 3]), &shard));
+        assert!(!return_value.answers(&request, &ShardIdentifier::default()));
+
+        let mut replayed = return_value;
+        replayed.request_hash = request_hash("chain_getHeader", &[1, 2, 3]);
+        assert!(!replayed.verify_signature(&signer));
+    }
+
+    #[test]
+    fn verifying_unsigned_or_tampered_return_value_fails() {
+        let signer_pair = ed25519::Pair::from_string("//Alice", None).unwrap();
+        let other: AccountId32 = ed25519::Pair::from_string("//Bob", None)
+            .unwrap()
+            .public()
+            .into();
+        let signer: AccountId32 = signer_pair.public().into();
+
+        assert!(!test_return_value().verify_signature(&signer));
+
+        let mut tampered = test_return_value().sign(&signer_pair);
+        tampered.do_watch = false;
+        assert!(!tampered.verify_signature(&signer));
+
+        assert!(!test_return_value()
+            .sign(&signer_pair)
+            .verify_signature(&other));
+    }
+}
@@ -0,0 +1,235 @@
+/*
+    Copyright 2019 Supercomputing Systems AG
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+
+*/
+
+//! Block production settings and shard genesis configs of the worker.
+//!
+//! Block production settings are read from a json config file, e.g.
+//! `{ "block_time": 1000, "call_timeout": 300, "max_operations_per_block": 1000 }`,
+//! and can be overridden by the respective command line arguments.
+//! Missing values fall back to their defaults.
+//!
+//! The genesis config of a shard is read from a json file with ss58 encoded accounts, e.g.
+//! `{ "sudo": "5Grw...", "balances": [["5Grw...", 1000]], "assets": [1], "asset_balances": [["5Grw...", 1, 500]] }`.
+//! Missing values are left empty.
+
+use std::fmt::Display;
+use std::fs;
+use std::str::FromStr;
+
+use clap::ArgMatches;
+use serde_derive::Deserialize;
+use sp_core::crypto::{AccountId32, Ss58Codec};
+
+use my_node_runtime::Balance;
+use substratee_stf::{AssetId, GenesisConfig};
+use substratee_worker_primitives::BlockProductionConfig;
+
+pub fn block_production_config(matches: &ArgMatches<'_>) -> Result<BlockProductionConfig, String> {
+    let mut config = match matches.value_of("config") {
+        Some(path) => read_config_file(path)?,
+        None => BlockProductionConfig::default(),
+    };
+
+    if let Some(block_time) = parse_arg(matches, "block-time")? {
+        config.block_time = block_time;
+    }
+    if let Some(call_timeout) = parse_arg(matches, "call-timeout")? {
+        config.call_timeout = call_timeout;
+    }
+    if let Some(max_operations) = parse_arg(matches, "max-ops-per-block")? {
+        config.max_operations_per_block = max_operations;
+    }
+    if let Some(retention) = parse_arg(matches, "relay-header-retention")? {
+        config.relay_header_retention = retention;
+    }
+    if let Some(timeout) = parse_arg(matches, "xt-inclusion-timeout")? {
+        config.xt_inclusion_timeout = timeout;
+    }
+    Ok(config)
+}
+
+/// Json representation of the `GenesisConfig`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GenesisConfigFile {
+    sudo: Option<String>,
+    balances: Vec<(String, Balance)>,
+    assets: Vec<AssetId>,
+    asset_balances: Vec<(String, AssetId, Balance)>,
+}
+
+pub fn genesis_config(matches: &ArgMatches<'_>) -> Result<GenesisConfig, String> {
+    let path = match matches.value_of("genesis") {
+        Some(path) => path,
+        None => return Ok(GenesisConfig::default()),
+    };
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read genesis file {}: {}", path, e))?;
+    let file: GenesisConfigFile = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid genesis file {}: {}", path, e))?;
+
+    let balances = file
+        .balances
+        .into_iter()
+        .map(|(who, free)| Ok((parse_account(&who)?, free)))
+        .collect::<Result<_, String>>()?;
+    let asset_balances = file
+        .asset_balances
+        .into_iter()
+        .map(|(who, asset_id, free)| Ok((parse_account(&who)?, asset_id, free)))
+        .collect::<Result<_, String>>()?;
+    Ok(GenesisConfig {
+        sudo: file.sudo.as_deref().map(parse_account).transpose()?,
+        balances,
+        assets: file.assets,
+        asset_balances,
+    })
+}
+
+fn parse_account(account: &str) -> Result<AccountId32, String> {
+    AccountId32::from_ss58check(account)
+        .map_err(|e| format!("Invalid ss58 account {}: {:?}", account, e))
+}
+
+fn read_config_file(path: &str) -> Result<BlockProductionConfig, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read config file {}: {}", path, e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Invalid config file {}: {}", path, e))
+}
+
+fn parse_arg<T>(matches: &ArgMatches<'_>, name: &str) -> Result<Option<T>, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match matches.value_of(name) {
+        Some(value) => value
+            .parse()
+            .map(Some)
+            .map_err(|e| format!("Invalid value for --{}: {}", name, e)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{load_yaml, App};
+    use sp_keyring::AccountKeyring;
+
+    fn config_from_args(args: &[&str]) -> Result<BlockProductionConfig, String> {
+        let yml = load_yaml!("cli.yml");
+        let matches = App::from_yaml(yml).get_matches_from(args);
+        block_production_config(&matches)
+    }
+
+    #[test]
+    fn default_config_is_used_without_arguments() {
+        let config = config_from_args(&["substratee-worker", "mrenclave"]).unwrap();
+
+        assert_eq!(config, BlockProductionConfig::default());
+    }
+
+    #[test]
+    fn arguments_override_config_file() {
+        let path = std::env::temp_dir().join("worker_config_test.json");
+        fs::write(&path, r#"{ "block_time": 2000, "call_timeout": 500 }"#).unwrap();
+
+        let config = config_from_args(&[
+            "substratee-worker",
+            "--config",
+            path.to_str().unwrap(),
+            "--call-timeout",
+            "150",
+            "--max-ops-per-block",
+            "10",
+            "mrenclave",
+        ])
+        .unwrap();
+
+        assert_eq!(config.block_time, 2000);
+        assert_eq!(config.call_timeout, 150);
+        assert_eq!(
+            config.relay_header_retention,
+            BlockProductionConfig::default().relay_header_retention
+        );
+        assert_eq!(config.max_operations_per_block, 10);
+    }
+
+    #[test]
+    fn invalid_argument_fails() {
+        assert!(
+            config_from_args(&["substratee-worker", "--block-time", "soon", "mrenclave"]).is_err()
+        );
+    }
+
+    fn genesis_from_args(args: &[&str]) -> Result<GenesisConfig, String> {
+        let yml = load_yaml!("cli.yml");
+        let matches = App::from_yaml(yml).get_matches_from(args);
+        genesis_config(matches.subcommand_matches("init-shard").unwrap())
+    }
+
+    #[test]
+    fn empty_genesis_is_used_without_file() {
+        let genesis = genesis_from_args(&["substratee-worker", "init-shard"]).unwrap();
+
+        assert_eq!(genesis, GenesisConfig::default());
+    }
+
+    #[test]
+    fn genesis_file_is_parsed() {
+        let alice = AccountKeyring::Alice.to_account_id();
+        let bob = AccountKeyring::Bob.to_account_id();
+        let path = std::env::temp_dir().join("worker_genesis_test.json");
+        fs::write(
+            &path,
+            format!(
+                r#"{{ "sudo": "{}", "balances": [["{}", 1000]], "assets": [1] }}"#,
+                alice.to_ss58check(),
+                bob.to_ss58check()
+            ),
+        )
+        .unwrap();
+
+        let genesis = genesis_from_args(&[
+            "substratee-worker",
+            "init-shard",
+            "--genesis",
+            path.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        assert_eq!(genesis.sudo, Some(alice));
+        assert_eq!(genesis.balances, vec![(bob, 1000)]);
+        assert_eq!(genesis.assets, vec![1]);
+        assert!(genesis.asset_balances.is_empty());
+    }
+
+    #[test]
+    fn genesis_with_invalid_account_fails() {
+        let path = std::env::temp_dir().join("worker_invalid_genesis_test.json");
+        fs::write(&path, r#"{ "sudo": "not an account" }"#).unwrap();
+
+        assert!(genesis_from_args(&[
+            "substratee-worker",
+            "init-shard",
+            "--genesis",
+            path.to_str().unwrap(),
+        ])
+        .is_err());
+    }
+}
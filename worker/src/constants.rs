@@ -17,6 +17,7 @@
 
 pub static ENCRYPTED_STATE_FILE: &str = "state.bin";
 pub static SHARDS_PATH: &str = "./shards";
+pub static SIDECHAIN_STORAGE_PATH: &str = "./sidechain_db";
 pub static ENCLAVE_TOKEN: &str = "../bin/enclave.token";
 pub static ENCLAVE_FILE: &str = "../bin/enclave.signed.so";
 pub static SHIELDING_KEY_FILE: &str = "enclave-shielding-pubkey.json";
@@ -26,13 +26,32 @@ use sgx_urts::SgxEnclave;
 
 use crate::constants::{ENCLAVE_FILE, ENCLAVE_TOKEN, EXTRINSIC_MAX_SIZE, STATE_VALUE_MAX_SIZE};
 use codec::{Decode, Encode};
-use my_node_runtime::{Header, SignedBlock};
+use my_node_runtime::{pallet_substratee_registry::ShardIdentifier, Hash, Header, SignedBlock};
 use sp_core::ed25519;
-use sp_finality_grandpa::VersionedAuthorityList;
+use sp_finality_grandpa::{SetId, VersionedAuthorityList};
+use substratee_stf::GenesisConfig;
+use substratee_worker_primitives::block::SignedBlock as SignedSidechainBlock;
+use substratee_worker_primitives::{BlockProductionConfig, RelayId};
 
 extern "C" {
     fn init(eid: sgx_enclave_id_t, retval: *mut sgx_status_t) -> sgx_status_t;
 
+    fn set_block_production_config(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        config: *const u8,
+        config_size: usize,
+    ) -> sgx_status_t;
+
+    fn init_shard(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        shard: *const u8,
+        shard_size: u32,
+        genesis_config: *const u8,
+        genesis_config_size: usize,
+    ) -> sgx_status_t;
+
     fn get_state(
         eid: sgx_enclave_id_t,
         retval: *mut sgx_status_t,
@@ -49,10 +68,15 @@ extern "C" {
         retval: *mut sgx_status_t,
         genesis_hash: *const u8,
         genesis_hash_size: usize,
+        header: *const u8,
+        header_size: usize,
         authority_list: *const u8,
         authority_list_size: usize,
+        authority_set_id: u64,
         authority_proof: *const u8,
         authority_proof_size: usize,
+        layer_one: u8,
+        relay_id: *mut u64,
         latest_header: *mut u8,
         latest_header_size: usize,
     ) -> sgx_status_t;
@@ -60,11 +84,29 @@ extern "C" {
     fn produce_blocks(
         eid: sgx_enclave_id_t,
         retval: *mut sgx_status_t,
+        relay_id: u64,
+        blocks: *const u8,
+        blocks_size: usize,
+        nonce: *const u32,
+    ) -> sgx_status_t;
+
+    fn sync_chain_relay(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        relay_id: u64,
+        layer_one_relay_id: u64,
         blocks: *const u8,
         blocks_size: usize,
         nonce: *const u32,
     ) -> sgx_status_t;
 
+    fn import_sidechain_blocks(
+        eid: sgx_enclave_id_t,
+        retval: *mut sgx_status_t,
+        blocks: *const u8,
+        blocks_size: usize,
+    ) -> sgx_status_t;
+
     fn get_rsa_encryption_pubkey(
         eid: sgx_enclave_id_t,
         retval: *mut sgx_status_t,
@@ -190,12 +232,72 @@ pub fn enclave_init() -> SgxResult<SgxEnclave> {
     Ok(enclave)
 }
 
+/// Sets the timeouts and limits the enclave respects during block production
+pub fn enclave_set_block_production_config(
+    eid: sgx_enclave_id_t,
+    config: &BlockProductionConfig,
+) -> SgxResult<()> {
+    let mut status = sgx_status_t::SGX_SUCCESS;
+
+    let result = unsafe {
+        config.using_encoded(|c| set_block_production_config(eid, &mut status, c.as_ptr(), c.len()))
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(status);
+    }
+    if result != sgx_status_t::SGX_SUCCESS {
+        return Err(result);
+    }
+
+    Ok(())
+}
+
+pub fn enclave_init_shard(
+    eid: sgx_enclave_id_t,
+    shard: &ShardIdentifier,
+    genesis_config: &GenesisConfig,
+) -> SgxResult<()> {
+    let mut status = sgx_status_t::SGX_SUCCESS;
+
+    let result = unsafe {
+        genesis_config.using_encoded(|c| {
+            init_shard(
+                eid,
+                &mut status,
+                shard.as_ptr(),
+                shard.len() as u32,
+                c.as_ptr(),
+                c.len(),
+            )
+        })
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(status);
+    }
+    if result != sgx_status_t::SGX_SUCCESS {
+        return Err(result);
+    }
+
+    Ok(())
+}
+
+/// Initializes the chain relay from a trusted finalized `header` of the chain with `genesis_hash`
+///
+/// Returns the id of the relay following the chain and its latest finalized header.
+/// The relay is reused if the chain is already followed. `layer_one` marks the chain
+/// the sidechain blocks are confirmed on.
 pub fn enclave_init_chain_relay(
     eid: sgx_enclave_id_t,
-    genesis_header: Header,
+    genesis_hash: Hash,
+    header: Header,
     authority_list: VersionedAuthorityList,
+    authority_set_id: SetId,
     authority_proof: Vec<Vec<u8>>,
-) -> SgxResult<Header> {
+    layer_one: bool,
+) -> SgxResult<(RelayId, Header)> {
+    let mut relay_id: RelayId = 0;
     let mut latest_header = vec![0u8; 200];
 
     let mut status = sgx_status_t::SGX_SUCCESS;
@@ -205,12 +307,17 @@ pub fn enclave_init_chain_relay(
             init_chain_relay(
                 eid,
                 &mut status,
-                genesis_header.encode().as_ptr(),
-                genesis_header.encode().len(),
+                genesis_hash.encode().as_ptr(),
+                genesis_hash.encode().len(),
+                header.encode().as_ptr(),
+                header.encode().len(),
                 authorities.as_ptr(),
                 authorities.len(),
+                authority_set_id,
                 authority_proof.encode().as_ptr(),
                 authority_proof.encode().len(),
+                layer_one as u8,
+                &mut relay_id,
                 latest_header.as_mut_ptr(),
                 latest_header.len(),
             )
@@ -224,24 +331,86 @@ pub fn enclave_init_chain_relay(
         return Err(result);
     }
     let latest: Header = Decode::decode(&mut latest_header.as_slice()).unwrap();
-    info!("Latest Header {:?}", latest);
+    info!("Relay {}: Latest Header {:?}", relay_id, latest);
 
-    Ok(latest)
+    Ok((relay_id, latest))
 }
 
 /// Starts block production within enclave
 ///
-/// Returns the produced blocks
+/// `blocks_to_sync` are the new blocks of the chain followed by `relay_id`,
+/// on which the sidechain blocks are confirmed and the enclave account has `tee_nonce`.
 pub fn enclave_produce_blocks(
     eid: sgx_enclave_id_t,
+    relay_id: RelayId,
+    blocks_to_sync: Vec<SignedBlock>,
+    tee_nonce: u32,
+) -> SgxResult<()> {
+    let mut status = sgx_status_t::SGX_SUCCESS;
+
+    let result = unsafe {
+        blocks_to_sync.using_encoded(|b| {
+            produce_blocks(eid, &mut status, relay_id, b.as_ptr(), b.len(), &tee_nonce)
+        })
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(status);
+    }
+    if result != sgx_status_t::SGX_SUCCESS {
+        return Err(result);
+    }
+
+    Ok(())
+}
+
+/// Syncs the relay of an observed chain with its new blocks
+///
+/// The extrinsics resulting from the blocks are sent to the chain followed by
+/// `layer_one_relay_id`, `tee_nonce` being the nonce of the enclave account there.
+pub fn enclave_sync_chain_relay(
+    eid: sgx_enclave_id_t,
+    relay_id: RelayId,
+    layer_one_relay_id: RelayId,
     blocks_to_sync: Vec<SignedBlock>,
     tee_nonce: u32,
 ) -> SgxResult<()> {
     let mut status = sgx_status_t::SGX_SUCCESS;
 
     let result = unsafe {
-        blocks_to_sync
-            .using_encoded(|b| produce_blocks(eid, &mut status, b.as_ptr(), b.len(), &tee_nonce))
+        blocks_to_sync.using_encoded(|b| {
+            sync_chain_relay(
+                eid,
+                &mut status,
+                relay_id,
+                layer_one_relay_id,
+                b.as_ptr(),
+                b.len(),
+                &tee_nonce,
+            )
+        })
+    };
+
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(status);
+    }
+    if result != sgx_status_t::SGX_SUCCESS {
+        return Err(result);
+    }
+
+    Ok(())
+}
+
+/// Imports sidechain blocks of other enclaves by applying their state diffs
+pub fn enclave_import_sidechain_blocks(
+    eid: sgx_enclave_id_t,
+    blocks_to_import: Vec<SignedSidechainBlock>,
+) -> SgxResult<()> {
+    let mut status = sgx_status_t::SGX_SUCCESS;
+
+    let result = unsafe {
+        blocks_to_import
+            .using_encoded(|b| import_sidechain_blocks(eid, &mut status, b.as_ptr(), b.len()))
     };
 
     if status != sgx_status_t::SGX_SUCCESS {
@@ -18,7 +18,7 @@ use sgx_types::*;
 
 use codec::{Decode, Encode};
 use log::*;
-use sp_core::H256 as Hash;
+use sp_core::{sr25519, H256 as Hash};
 use std::collections::HashMap;
 use std::slice;
 use std::sync::{
@@ -26,13 +26,21 @@ use std::sync::{
     Arc, Mutex, MutexGuard,
 };
 use std::thread;
+use substrate_api_client::Api;
 use ws::{listen, CloseCode, Handler, Message, Result, Sender};
 
+use substratee_worker_primitives::block::{BlockId, SignedBlock as SignedSidechainBlock};
 use substratee_worker_primitives::{
-    DirectRequestStatus, RpcResponse, RpcReturnValue, TrustedOperationStatus,
+    BlockNumber, DirectRequestStatus, RpcRequest, RpcResponse, RpcReturnValue, ShardIdentifier,
 };
 
+use crate::constants::SIDECHAIN_STORAGE_PATH;
+use crate::enclave::api::enclave_import_sidechain_blocks;
+use crate::sidechain_gossip;
+use crate::sidechain_storage::SidechainStorage;
+
 static WATCHED_LIST: AtomicPtr<()> = AtomicPtr::new(0 as *mut ());
+static HEAD_SUBSCRIBERS: AtomicPtr<()> = AtomicPtr::new(0 as *mut ());
 static EID: AtomicPtr<u64> = AtomicPtr::new(0 as *mut sgx_enclave_id_t);
 
 extern "C" {
@@ -121,6 +129,11 @@ pub fn start_worker_api_direct_server(addr: String, eid: sgx_enclave_id_t) {
     let ptr = Arc::into_raw(pool_ptr);
     WATCHED_LIST.store(ptr as *mut (), Ordering::SeqCst);
 
+    // initialize static pointer to empty map of sidechain head subscribers
+    let subscribers: HeadSubscribers = HashMap::new();
+    let subscribers_ptr = Arc::into_raw(Arc::new(Mutex::new(subscribers)));
+    HEAD_SUBSCRIBERS.store(subscribers_ptr as *mut (), Ordering::SeqCst);
+
     // ensure top pool is initialised before returning
     init.join().unwrap();
     println!("Successfully initialised top pool");
@@ -140,8 +153,71 @@ fn load_watched_list() -> Option<&'static Mutex<HashMap<Hash, WatchingClient>>>
     }
 }
 
+type HeadSubscribers = HashMap<ShardIdentifier, Vec<WatchingClient>>;
+
+fn load_head_subscribers() -> Option<&'static Mutex<HeadSubscribers>> {
+    let ptr = HEAD_SUBSCRIBERS.load(Ordering::SeqCst) as *mut Mutex<HeadSubscribers>;
+    if ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { &*ptr })
+    }
+}
+
+// returns the shard if the request is a subscription to the sidechain heads
+fn subscribed_shard(request: &str) -> Option<ShardIdentifier> {
+    let rpc_request = serde_json::from_str::<RpcRequest>(request).ok()?;
+    if rpc_request.method != "chain_subscribeAllHeads" {
+        return None;
+    }
+    ShardIdentifier::decode(&mut rpc_request.params.as_slice()).ok()
+}
+
+/// Pushes the headers of the blocks to the clients subscribed to the heads of their shard
+///
+/// The headers are pushed by the untrusted worker, so they are sent unsigned and clients
+/// have to accept them explicitly, see `RpcReturnValue::is_untrusted`.
+pub fn notify_head_subscribers(blocks: &[SignedSidechainBlock]) {
+    let mutex = match load_head_subscribers() {
+        Some(mutex) => mutex,
+        // direct invocation server is not running
+        None => return,
+    };
+    let mut subscribers = mutex.lock().unwrap();
+    for signed_block in blocks.iter() {
+        let header = signed_block.block().header();
+        if let Some(clients) = subscribers.get_mut(&header.shard_id) {
+            let result = RpcReturnValue::new(header.encode(), true, DirectRequestStatus::Ok);
+            // drop the clients which have closed their connection
+            clients.retain(|watching_client| {
+                let response = RpcResponse {
+                    result: result.encode(),
+                    jsonrpc: watching_client.response.jsonrpc.clone(),
+                    id: watching_client.response.id,
+                };
+                watching_client
+                    .client
+                    .send(serde_json::to_string(&response).unwrap())
+                    .is_ok()
+            });
+        }
+    }
+}
+
 pub fn handle_direct_invocation_request(req: DirectWsServerRequest) -> Result<()> {
     info!("Got message '{:?}'. ", req.request);
+    // sidechain blocks are stored outside of the enclave
+    if let Ok(rpc_request) = serde_json::from_str::<RpcRequest>(&req.request) {
+        match rpc_request.method.as_str() {
+            "sidechain_importBlock" => {
+                return handle_sidechain_import_request(req.client, rpc_request)
+            }
+            "chain_getBlock" | "chain_getHeader" | "chain_getBlockHash" => {
+                return handle_sidechain_query_request(req.client, rpc_request)
+            }
+            _ => (),
+        }
+    }
     let eid = unsafe { *EID.load(Ordering::SeqCst) };
     // forwarding rpc string directly to enclave
     let mut retval = sgx_status_t::SGX_SUCCESS;
@@ -199,6 +275,26 @@ pub fn handle_direct_invocation_request(req: DirectWsServerRequest) -> Result<()
                     }
                 }
             }
+            if result_of_rpc_response.status == DirectRequestStatus::Ok
+                && result_of_rpc_response.do_watch
+            {
+                // subscribe to the sidechain heads of the shard
+                if let Some(shard) = subscribed_shard(&req.request) {
+                    let mutex = load_head_subscribers().unwrap();
+                    let mut subscribers = mutex.lock().unwrap();
+                    subscribers
+                        .entry(shard)
+                        .or_insert_with(Vec::new)
+                        .push(WatchingClient {
+                            client: req.client.clone(),
+                            response: RpcResponse {
+                                result: result_of_rpc_response.encode(),
+                                jsonrpc: full_rpc_response.jsonrpc.clone(),
+                                id: full_rpc_response.id,
+                            },
+                        });
+                }
+            }
         }
         return req
             .client
@@ -208,42 +304,125 @@ pub fn handle_direct_invocation_request(req: DirectWsServerRequest) -> Result<()
     req.client.send(decoded_response)
 }
 
+fn handle_sidechain_import_request(client: Sender, rpc_request: RpcRequest) -> Result<()> {
+    let result = match Vec::<SignedSidechainBlock>::decode(&mut rpc_request.params.as_slice()) {
+        Ok(blocks) => {
+            let eid = unsafe { *EID.load(Ordering::SeqCst) };
+            let storage = SidechainStorage::new(SIDECHAIN_STORAGE_PATH);
+            match Api::<sr25519::Pair>::new(crate::NODE_URL.lock().unwrap().clone()) {
+                Ok(api) => {
+                    let registered_enclaves = sidechain_gossip::registered_enclaves(&api);
+                    sidechain_gossip::import_blocks(
+                        &storage,
+                        blocks,
+                        &registered_enclaves,
+                        |block| {
+                            enclave_import_sidechain_blocks(eid, vec![block.clone()]).map_err(
+                                |e| format!("Enclave could not import block: {}", e.as_str()),
+                            )?;
+                            notify_head_subscribers(&[block.clone()]);
+                            Ok(())
+                        },
+                    )
+                }
+                Err(e) => Err(format!("Could not connect to the node: {:?}", e)),
+            }
+        }
+        Err(_) => Err("Could not decode sidechain blocks".to_owned()),
+    };
+    let return_value = match result {
+        Ok(()) => RpcReturnValue::new(vec![], false, DirectRequestStatus::Ok),
+        Err(msg) => {
+            warn!("Rejected sidechain blocks from peer: {}", msg);
+            RpcReturnValue::new(msg.encode(), false, DirectRequestStatus::Error)
+        }
+    };
+    let response = RpcResponse {
+        jsonrpc: rpc_request.jsonrpc,
+        result: return_value.encode(),
+        id: rpc_request.id as u32,
+    };
+    client.send(serde_json::to_string(&response).unwrap())
+}
+
+// the blocks are stored outside of the enclave, so the answer is unsigned and marked untrusted
+fn handle_sidechain_query_request(client: Sender, rpc_request: RpcRequest) -> Result<()> {
+    let storage = SidechainStorage::new(SIDECHAIN_STORAGE_PATH);
+    let return_value =
+        match query_sidechain_storage(&storage, &rpc_request.method, &rpc_request.params) {
+            Ok(value) => RpcReturnValue::new(value, false, DirectRequestStatus::Ok),
+            Err(_) => RpcReturnValue::new(
+                "Could not decode request parameters".to_owned().encode(),
+                false,
+                DirectRequestStatus::Error,
+            ),
+        };
+    let response = RpcResponse {
+        jsonrpc: rpc_request.jsonrpc,
+        result: return_value.encode(),
+        id: rpc_request.id as u32,
+    };
+    client.send(serde_json::to_string(&response).unwrap())
+}
+
+// returns the encoded answer to a sidechain block query
+fn query_sidechain_storage(
+    storage: &SidechainStorage,
+    method: &str,
+    mut params: &[u8],
+) -> std::result::Result<Vec<u8>, codec::Error> {
+    match method {
+        // params: encoded (ShardIdentifier, BlockId)
+        "chain_getBlock" => {
+            let (shard, id) = <(ShardIdentifier, BlockId)>::decode(&mut params)?;
+            Ok(storage.get_block(&shard, &id).encode())
+        }
+        // params: encoded (ShardIdentifier, BlockId)
+        "chain_getHeader" => {
+            let (shard, id) = <(ShardIdentifier, BlockId)>::decode(&mut params)?;
+            let header = storage
+                .get_block(&shard, &id)
+                .map(|signed_block| signed_block.block().header());
+            Ok(header.encode())
+        }
+        // params: encoded (ShardIdentifier, Option<BlockNumber>), `None` returns the head
+        _ => {
+            let (shard, number) = <(ShardIdentifier, Option<BlockNumber>)>::decode(&mut params)?;
+            let hash = match number {
+                Some(number) => storage.get_block_hash(&shard, number),
+                None => storage.get_head(&shard).map(|(_, hash)| hash),
+            };
+            Ok(hash.encode())
+        }
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn ocall_update_status_event(
     hash_encoded: *const u8,
     hash_size: u32,
-    status_update_encoded: *const u8,
-    status_size: u32,
+    return_value_encoded: *const u8,
+    return_value_size: u32,
 ) -> sgx_status_t {
-    let mut status_update_slice =
-        slice::from_raw_parts(status_update_encoded, status_size as usize);
-    let status_update = TrustedOperationStatus::decode(&mut status_update_slice).unwrap();
+    // the return value is composed and signed by the enclave and forwarded as is
+    let return_value_slice =
+        slice::from_raw_parts(return_value_encoded, return_value_size as usize);
+    let continue_watching = match RpcReturnValue::decode(&mut &return_value_slice[..]) {
+        Ok(return_value) => return_value.do_watch,
+        Err(_) => {
+            error!("Could not decode the status update of the enclave");
+            return sgx_status_t::SGX_ERROR_UNEXPECTED;
+        }
+    };
     let mut hash_slice = slice::from_raw_parts(hash_encoded, hash_size as usize);
     if let Ok(hash) = Hash::decode(&mut hash_slice) {
         // Aquire watched list lock
         let mutex = load_watched_list().unwrap();
         let mut watch_list = mutex.lock().unwrap();
-        let mut continue_watching = true;
         if let Some(client_event) = watch_list.get_mut(&hash) {
             let mut event = &mut client_event.response;
-            // Aquire result of old RpcResponse
-            let old_result: Vec<u8> = event.result.clone();
-            let mut result = RpcReturnValue::decode(&mut old_result.as_slice()).unwrap();
-
-            match status_update {
-                TrustedOperationStatus::Invalid
-                | TrustedOperationStatus::InSidechainBlock(_)
-                | TrustedOperationStatus::Finalized
-                | TrustedOperationStatus::Usurped => {
-                    // Stop watching
-                    result.do_watch = false;
-                    continue_watching = false;
-                }
-                _ => {}
-            };
             // update response
-            result.status = DirectRequestStatus::TrustedOperationStatus(status_update);
-            event.result = result.encode();
+            event.result = return_value_slice.to_vec();
             client_event
                 .client
                 .send(serde_json::to_string(&event).unwrap())
@@ -252,8 +431,6 @@ pub unsafe extern "C" fn ocall_update_status_event(
             if !continue_watching {
                 client_event.client.close(CloseCode::Normal).unwrap();
             }
-        } else {
-            continue_watching = false;
         }
         if !continue_watching {
             watch_list.remove(&hash);
@@ -262,40 +439,3 @@ pub unsafe extern "C" fn ocall_update_status_event(
 
     sgx_status_t::SGX_SUCCESS
 }
-
-#[no_mangle]
-pub unsafe extern "C" fn ocall_send_status(
-    hash_encoded: *const u8,
-    hash_size: u32,
-    status_encoded: *const u8,
-    status_size: u32,
-) -> sgx_status_t {
-    let status_slice = slice::from_raw_parts(status_encoded, status_size as usize);
-    let mut hash_slice = slice::from_raw_parts(hash_encoded, hash_size as usize);
-    if let Ok(hash) = Hash::decode(&mut hash_slice) {
-        // Aquire watched list lock
-        let mutex = load_watched_list().unwrap();
-        let mut guard = mutex.lock().unwrap();
-        if let Some(client_response) = guard.get_mut(&hash) {
-            let mut response = &mut client_response.response;
-
-            // create return value
-            // TODO: Signature?
-            let submitted =
-                DirectRequestStatus::TrustedOperationStatus(TrustedOperationStatus::Submitted);
-            let result = RpcReturnValue::new(status_slice.to_vec(), false, submitted);
-
-            // update response
-            response.result = result.encode();
-            client_response
-                .client
-                .send(serde_json::to_string(&response).unwrap())
-                .unwrap();
-
-            client_response.client.close(CloseCode::Normal).unwrap();
-        }
-        guard.remove(&hash);
-    }
-
-    sgx_status_t::SGX_SUCCESS
-}
@@ -16,7 +16,6 @@
 */
 use std::fs::{self, File};
 use std::io::stdin;
-use std::io::Write;
 use std::path::Path;
 use std::slice;
 use std::str;
@@ -43,30 +42,39 @@ use sp_core::{
     Pair,
 };
 use sp_keyring::AccountKeyring;
-use substrate_api_client::{utils::FromHexString, Api, GenericAddress, XtStatus};
+use substrate_api_client::{
+    utils::{storage_key, FromHexString},
+    Api, GenericAddress, XtStatus,
+};
 
-use crate::enclave::api::{enclave_init_chain_relay, enclave_produce_blocks};
+use crate::enclave::api::{
+    enclave_init_chain_relay, enclave_produce_blocks, enclave_sync_chain_relay,
+};
 use enclave::api::{
-    enclave_dump_ra, enclave_init, enclave_mrenclave, enclave_perform_ra, enclave_shielding_key,
-    enclave_signing_key,
+    enclave_dump_ra, enclave_init, enclave_init_shard, enclave_mrenclave, enclave_perform_ra,
+    enclave_set_block_production_config, enclave_shielding_key, enclave_signing_key,
 };
 use enclave::tls_ra::{enclave_request_key_provisioning, enclave_run_key_provisioning_server};
-use enclave::worker_api_direct_server::start_worker_api_direct_server;
-use sp_finality_grandpa::{AuthorityList, VersionedAuthorityList, GRANDPA_AUTHORITIES_KEY};
+use enclave::worker_api_direct_server::{notify_head_subscribers, start_worker_api_direct_server};
+use sp_finality_grandpa::{AuthorityList, SetId, VersionedAuthorityList, GRANDPA_AUTHORITIES_KEY};
 use std::time::{Duration, SystemTime};
 
+use sidechain_storage::SidechainStorage;
+use substratee_stf::GenesisConfig;
 use substratee_worker_primitives::block::SignedBlock as SignedSidechainBlock;
+use substratee_worker_primitives::{BlockProductionConfig, RelayId};
 
+mod config;
 mod constants;
 mod enclave;
 mod ipfs;
+mod sidechain_gossip;
+mod sidechain_storage;
 mod tests;
 
 /// how many blocks will be synced before storing the chain db to disk
 const BLOCK_SYNC_BATCH_SIZE: u32 = 1000;
 const VERSION: &str = env!("CARGO_PKG_VERSION");
-/// start block production every ... ms
-const BLOCK_PRODUCTION_INTERVAL: u64 = 1000;
 
 fn main() {
     // Setup logging
@@ -117,6 +125,15 @@ fn main() {
         };
         println!("Advertising worker api at {}", ext_api_url);
         let skip_ra = smatches.is_present("skip-ra");
+        let warp_sync = smatches.is_present("warp-sync");
+        let observed_urls: Vec<String> = smatches
+            .values_of("observe")
+            .map(|urls| urls.map(String::from).collect())
+            .unwrap_or_default();
+        let block_production_config = match config::block_production_config(&matches) {
+            Ok(config) => config,
+            Err(msg) => panic!("{}", msg),
+        };
         worker(
             w_ip,
             mu_ra_port,
@@ -124,6 +141,9 @@ fn main() {
             &ext_api_url,
             worker_rpc_port,
             skip_ra,
+            warp_sync,
+            observed_urls,
+            block_production_config,
         );
     } else if let Some(smatches) = matches.subcommand_matches("request-keys") {
         let shard: ShardIdentifier = match smatches.value_of("shard") {
@@ -206,22 +226,33 @@ fn main() {
         return;
     }
     if let Some(_matches) = matches.subcommand_matches("init-shard") {
+        let genesis_config = match config::genesis_config(_matches) {
+            Ok(genesis_config) => genesis_config,
+            Err(e) => {
+                error!("{}", e);
+                return;
+            }
+        };
+        let enclave = enclave_init().unwrap();
         match _matches.values_of("shard") {
             Some(values) => {
                 for shard in values {
                     match shard.from_base58() {
                         Ok(s) => {
-                            init_shard(&ShardIdentifier::from_slice(&s[..]));
+                            init_shard(
+                                enclave.geteid(),
+                                &ShardIdentifier::from_slice(&s[..]),
+                                &genesis_config,
+                            );
                         }
                         _ => panic!("shard must be hex encoded"),
                     }
                 }
             }
             _ => {
-                let enclave = enclave_init().unwrap();
                 let shard =
                     ShardIdentifier::from_slice(&enclave_mrenclave(enclave.geteid()).unwrap());
-                init_shard(&shard);
+                init_shard(enclave.geteid(), &shard, &genesis_config);
             }
         };
     } else if let Some(_matches) = matches.subcommand_matches("test") {
@@ -261,6 +292,9 @@ fn worker(
     ext_api_url: &str,
     worker_rpc_port: &str,
     skip_ra: bool,
+    warp_sync: bool,
+    observed_urls: Vec<String>,
+    block_production_config: BlockProductionConfig,
 ) {
     println!("Encointer Worker v{}", VERSION);
     info!("starting worker on shard {}", shard.encode().to_base58());
@@ -278,6 +312,8 @@ fn worker(
     let mrenclave = enclave_mrenclave(enclave.geteid()).unwrap();
     println!("MRENCLAVE={}", mrenclave.to_base58());
     let eid = enclave.geteid();
+    info!("block production config: {:?}", block_production_config);
+    enclave_set_block_production_config(eid, &block_production_config).unwrap();
     // ------------------------------------------------------------------------
     // let new workers call us for key provisioning
     println!("MU-RA server listening on ws://{}:{}", w_ip, mu_ra_port);
@@ -335,15 +371,43 @@ fn worker(
         println!("[<] Extrinsic got finalized. Hash: {:?}\n", tx_hash);
     }
 
-    let latest_head = init_chain_relay(eid, &api);
+    let (relay_id, latest_head) = init_chain_relay(eid, &api, warp_sync, true);
+    let latest_head = produce_blocks(eid, &api, relay_id, latest_head);
     println!("*** [+] Finished syncing chain relay\n");
 
+    // ------------------------------------------------------------------------
+    // follow the observed chains, e.g. bridge chains for deposits
+    let observed_chains: Vec<ObservedChain> = observed_urls
+        .into_iter()
+        .map(|url| {
+            println!("*** Observing chain at {}", url);
+            let observed_api = Api::<sr25519::Pair>::new(url).unwrap();
+            let (observed_relay_id, head) = init_chain_relay(eid, &observed_api, warp_sync, false);
+            let mut chain = ObservedChain {
+                api: observed_api,
+                relay_id: observed_relay_id,
+                latest_head: head,
+            };
+            sync_observed_chain(eid, &api, relay_id, &mut chain);
+            chain
+        })
+        .collect();
+
     // ------------------------------------------------------------------------
     // start interval block production
     let api4 = api.clone();
     thread::Builder::new()
         .name("interval_block_production_timer".to_owned())
-        .spawn(move || start_interval_block_production(eid, &api4, latest_head))
+        .spawn(move || {
+            start_interval_block_production(
+                eid,
+                &api4,
+                relay_id,
+                latest_head,
+                observed_chains,
+                block_production_config.block_time,
+            )
+        })
         .unwrap();
 
     // ------------------------------------------------------------------------
@@ -377,20 +441,34 @@ fn worker(
     }
 }
 
+/// A chain followed by the chain relay besides the one the worker is registered on
+pub struct ObservedChain {
+    pub api: Api<sr25519::Pair>,
+    pub relay_id: RelayId,
+    pub latest_head: Header,
+}
+
 /// Triggers the enclave to produce a block based on a fixed time schedule
+/// and syncs the observed chains in between
 fn start_interval_block_production(
     eid: sgx_enclave_id_t,
     api: &Api<sr25519::Pair>,
+    relay_id: RelayId,
     mut latest_head: Header,
+    mut observed_chains: Vec<ObservedChain>,
+    block_time: u64,
 ) {
-    let block_production_interval = Duration::from_millis(BLOCK_PRODUCTION_INTERVAL);
+    let block_production_interval = Duration::from_millis(block_time);
     let mut interval_start = SystemTime::now();
     loop {
         if let Ok(elapsed) = interval_start.elapsed() {
             if elapsed >= block_production_interval {
                 // update interval time
                 interval_start = SystemTime::now();
-                latest_head = produce_blocks(eid, api, latest_head)
+                latest_head = produce_blocks(eid, api, relay_id, latest_head);
+                for chain in observed_chains.iter_mut() {
+                    sync_observed_chain(eid, api, relay_id, chain);
+                }
             } else {
                 // sleep for the rest of the interval
                 let sleep_time = block_production_interval - elapsed;
@@ -512,41 +590,73 @@ fn print_events(events: Events, _sender: Sender<String>) {
     }
 }
 
-pub fn init_chain_relay(eid: sgx_enclave_id_t, api: &Api<sr25519::Pair>) -> Header {
+/// Initializes the relay following the chain of `api`.
+///
+/// With `warp_sync`, a new relay starts from the latest finalized block
+/// instead of the genesis block. An already initialized relay is continued.
+///
+/// `layer_one` marks the chain the sidechain blocks are confirmed on.
+///
+/// Returns the relay id and its last synced header.
+pub fn init_chain_relay(
+    eid: sgx_enclave_id_t,
+    api: &Api<sr25519::Pair>,
+    warp_sync: bool,
+    layer_one: bool,
+) -> (RelayId, Header) {
     let genesis_hash = api.get_genesis_hash().unwrap();
-    let genesis_header: Header = api.get_header(Some(genesis_hash)).unwrap().unwrap();
-    info!("Got genesis Header: \n {:?} \n", genesis_header);
+    let start_hash = if warp_sync {
+        api.get_finalized_head().unwrap().unwrap()
+    } else {
+        genesis_hash
+    };
+    let start_header: Header = api.get_header(Some(start_hash)).unwrap().unwrap();
+    info!("Got start Header: \n {:?} \n", start_header);
     let grandpas: AuthorityList = api
         .get_storage_by_key_hash(
             StorageKey(GRANDPA_AUTHORITIES_KEY.to_vec()),
-            Some(genesis_header.hash()),
+            Some(start_hash),
         )
         .unwrap()
         .map(|g: VersionedAuthorityList| g.into())
         .unwrap();
+    let grandpa_set_id: SetId = api
+        .get_storage_value("GrandpaFinality", "CurrentSetId", Some(start_hash))
+        .unwrap()
+        .unwrap_or_default();
 
+    // the enclave checks the authorities and their set id against the proof
     let grandpa_proof = api
         .get_storage_proof_by_keys(
-            vec![StorageKey(GRANDPA_AUTHORITIES_KEY.to_vec())],
-            Some(genesis_header.hash()),
+            vec![
+                StorageKey(GRANDPA_AUTHORITIES_KEY.to_vec()),
+                storage_key("GrandpaFinality", "CurrentSetId"),
+                storage_key("GrandpaFinality", "PendingChange"),
+            ],
+            Some(start_hash),
         )
         .unwrap()
         .map(|read_proof| read_proof.proof.into_iter().map(|bytes| bytes.0).collect())
         .unwrap();
 
-    debug!("Grandpa Authority List: \n {:?} \n ", grandpas);
+    debug!(
+        "Grandpa Authority List of set {}: \n {:?} \n ",
+        grandpa_set_id, grandpas
+    );
 
-    let latest = enclave_init_chain_relay(
+    let relay = enclave_init_chain_relay(
         eid,
-        genesis_header,
+        genesis_hash,
+        start_header,
         VersionedAuthorityList::from(grandpas),
+        grandpa_set_id,
         grandpa_proof,
+        layer_one,
     )
     .unwrap();
 
-    info!("Finished initializing chain relay, syncing....");
-
-    produce_blocks(eid, api, latest)
+    info!("Finished initializing relay {}", relay.0);
+    relay
 }
 
 /// Starts block production
@@ -555,9 +665,40 @@ pub fn init_chain_relay(eid: sgx_enclave_id_t, api: &Api<sr25519::Pair>) -> Head
 pub fn produce_blocks(
     eid: sgx_enclave_id_t,
     api: &Api<sr25519::Pair>,
+    relay_id: RelayId,
     last_synced_head: Header,
 ) -> Header {
-    // obtain latest finalized block from layer one
+    let tee_accountid = enclave_account(eid);
+    sync_blocks(api, last_synced_head, |blocks| {
+        let tee_nonce = get_nonce(api, &tee_accountid);
+        enclave_produce_blocks(eid, relay_id, blocks, tee_nonce)
+    })
+}
+
+/// Syncs the relay of an observed chain to its latest finalized block.
+/// The resulting extrinsics are sent to layer one, followed by `layer_one_relay_id`.
+pub fn sync_observed_chain(
+    eid: sgx_enclave_id_t,
+    api: &Api<sr25519::Pair>,
+    layer_one_relay_id: RelayId,
+    chain: &mut ObservedChain,
+) {
+    let tee_accountid = enclave_account(eid);
+    let relay_id = chain.relay_id;
+    chain.latest_head = sync_blocks(&chain.api, chain.latest_head.clone(), |blocks| {
+        let tee_nonce = get_nonce(api, &tee_accountid);
+        enclave_sync_chain_relay(eid, relay_id, layer_one_relay_id, blocks, tee_nonce)
+    });
+}
+
+/// Feeds the blocks finalized on the chain of `api` since `last_synced_head` to `sync` in batches
+///
+/// Returns the last synced header
+fn sync_blocks<F>(api: &Api<sr25519::Pair>, last_synced_head: Header, mut sync: F) -> Header
+where
+    F: FnMut(Vec<SignedBlock>) -> SgxResult<()>,
+{
+    // obtain latest finalized block
     debug!("Getting current head");
     let curr_head: SignedBlock = api
         .get_finalized_head()
@@ -603,8 +744,6 @@ pub fn produce_blocks(
         blocks_to_sync.reverse();
     }
 
-    let tee_accountid = enclave_account(eid);
-
     // only feed BLOCK_SYNC_BATCH_SIZE blocks at a time into the enclave to save enclave state regularly
     let mut i = if curr_head.block.header.hash() == last_synced_head.hash() {
         curr_head.block.header.number as usize
@@ -612,9 +751,7 @@ pub fn produce_blocks(
         blocks_to_sync[0].block.header.number as usize
     };
     for chunk in blocks_to_sync.chunks(BLOCK_SYNC_BATCH_SIZE as usize) {
-        let tee_nonce = get_nonce(&api, &tee_accountid);
-        // Produce blocks
-        if let Err(e) = enclave_produce_blocks(eid, chunk.to_vec(), tee_nonce) {
+        if let Err(e) = sync(chunk.to_vec()) {
             error!("{}", e);
             // enclave might not have synced
             return last_synced_head;
@@ -636,13 +773,12 @@ fn hex_encode(data: Vec<u8>) -> String {
     hex_str
 }
 
-fn init_shard(shard: &ShardIdentifier) {
+fn init_shard(eid: sgx_enclave_id_t, shard: &ShardIdentifier, genesis_config: &GenesisConfig) {
     let path = format!("{}/{}", constants::SHARDS_PATH, shard.encode().to_base58());
     println!("initializing shard at {}", path);
-    fs::create_dir_all(path.clone()).expect("could not create dir");
 
-    let path = format!("{}/{}", path, constants::ENCRYPTED_STATE_FILE);
-    if Path::new(&path).exists() {
+    let state_path = format!("{}/{}", path, constants::ENCRYPTED_STATE_FILE);
+    if Path::new(&state_path).exists() {
         println!("shard state exists. Overwrite? [y/N]");
         let buffer = &mut String::new();
         stdin().read_line(buffer).unwrap();
@@ -651,8 +787,8 @@ fn init_shard(shard: &ShardIdentifier) {
             _ => return,
         }
     }
-    let mut file = fs::File::create(path).unwrap();
-    file.write_all(b"").unwrap();
+    // the enclave creates the shard and seals the genesis config, from which it builds the state
+    enclave_init_shard(eid, shard, genesis_config).expect("could not initialize shard");
 }
 
 // get the public signing key of the TEE
@@ -808,8 +944,10 @@ pub unsafe extern "C" fn ocall_send_block_and_confirmation(
             confirmation_calls.len()
         );
         for call in confirmation_calls.into_iter() {
-            api.send_extrinsic(hex_encode(call), XtStatus::Ready)
-                .unwrap();
+            // the enclave sends the extrinsic again if it is not included in time
+            if let Err(e) = api.send_extrinsic(hex_encode(call), XtStatus::Ready) {
+                error!("Sending extrinsic failed. Error: {:?}", e);
+            }
         }
         // await next block to avoid #37
         let (events_in, events_out) = channel();
@@ -828,9 +966,18 @@ pub unsafe extern "C" fn ocall_send_block_and_confirmation(
             vec![]
         }
     };
-    println! {"Received blocks: {:?}", signed_blocks};
-    // TODO: M8.3: Store blocks
-    // TODO: M8.3: broadcast blocks
+    debug!("Received blocks: {:?}", signed_blocks);
+    let sidechain_storage = SidechainStorage::new(constants::SIDECHAIN_STORAGE_PATH);
+    if let Err(e) = sidechain_storage.store_blocks(&signed_blocks) {
+        error!("Could not store sidechain blocks: {:?}", e);
+        status = sgx_status_t::SGX_ERROR_UNEXPECTED;
+    }
+    notify_head_subscribers(&signed_blocks);
+
+    // gossip blocks to the other registered workers
+    if !signed_blocks.is_empty() {
+        thread::spawn(move || sidechain_gossip::broadcast_blocks(&api, signed_blocks));
+    }
     status
 }
 
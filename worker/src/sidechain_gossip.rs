@@ -0,0 +1,302 @@
+/*
+    Copyright 2019 Supercomputing Systems AG
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+
+*/
+
+//! Gossiping of sidechain blocks between the workers registered in the SubstrateeRegistry.
+
+use std::str;
+
+use base58::ToBase58;
+use codec::Encode;
+use log::*;
+use sp_core::sr25519;
+use substrate_api_client::Api;
+
+use substratee_node_primitives::calls::{get_worker_amount, get_worker_info, AccountId};
+use substratee_worker_api::direct_client::DirectApi;
+use substratee_worker_primitives::block::SignedBlock as SignedSidechainBlock;
+
+use crate::sidechain_storage::SidechainStorage;
+
+/// Sends the blocks to all registered workers except their author
+pub fn broadcast_blocks(api: &Api<sr25519::Pair>, blocks: Vec<SignedSidechainBlock>) {
+    if blocks.is_empty() {
+        return;
+    }
+    let author = blocks[0].block().block_author().clone();
+    let amount = match get_worker_amount(api) {
+        Some(amount) => amount,
+        None => {
+            debug!("No workers registered, not broadcasting sidechain blocks");
+            return;
+        }
+    };
+
+    // the registry starts indexing its map at one
+    for index in 1..=amount {
+        let worker = match get_worker_info(api, index) {
+            Some(worker) => worker,
+            None => continue,
+        };
+        if worker.pubkey == author {
+            continue;
+        }
+        let url = match str::from_utf8(&worker.url) {
+            Ok(url) => url.to_string(),
+            Err(_) => {
+                warn!("Worker {} has registered an invalid url", index);
+                continue;
+            }
+        };
+        debug!("Broadcasting {} sidechain blocks to {}", blocks.len(), url);
+        if let Err(e) = DirectApi::new(url.clone()).send_sidechain_blocks(blocks.clone()) {
+            warn!("Could not broadcast sidechain blocks to {}: {}", url, e);
+        }
+    }
+}
+
+/// Returns the accounts of all enclaves registered in the SubstrateeRegistry
+pub fn registered_enclaves(api: &Api<sr25519::Pair>) -> Vec<AccountId> {
+    // the registry starts indexing its map at one
+    let amount = get_worker_amount(api).unwrap_or(0);
+    (1..=amount)
+        .filter_map(|index| get_worker_info(api, index))
+        .map(|worker| worker.pubkey)
+        .collect()
+}
+
+/// Verifies the blocks received from a peer worker, imports them and stores them
+///
+/// A block is only accepted if it is signed by one of the `registered_enclaves`
+/// and it extends the current head of its shard. `import` is called for every
+/// accepted block before it is stored.
+pub fn import_blocks<F>(
+    storage: &SidechainStorage,
+    blocks: Vec<SignedSidechainBlock>,
+    registered_enclaves: &[AccountId],
+    import: F,
+) -> Result<(), String>
+where
+    F: Fn(&SignedSidechainBlock) -> Result<(), String>,
+{
+    for signed_block in blocks.into_iter() {
+        let block = signed_block.block();
+        let shard = block.shard_id();
+        let hash = block.hash();
+
+        if !signed_block.verify_signature() {
+            return Err(format!(
+                "Invalid signature of sidechain block #{}",
+                block.block_number()
+            ));
+        }
+
+        if !registered_enclaves.contains(block.block_author()) {
+            return Err(format!(
+                "Author of sidechain block #{} is not a registered enclave",
+                block.block_number()
+            ));
+        }
+
+        if storage.get_block_by_hash(&shard, &hash).is_some() {
+            debug!("Sidechain block {:?} is already known", hash);
+            continue;
+        }
+
+        if let Some((head_number, head_hash)) = storage.get_head(&shard) {
+            if block.parent_hash() != head_hash || block.block_number() != head_number + 1 {
+                return Err(format!(
+                    "Sidechain block #{} does not extend head #{} of shard {}",
+                    block.block_number(),
+                    head_number,
+                    shard.encode().to_base58()
+                ));
+            }
+        }
+
+        import(&signed_block)?;
+        storage
+            .store_block(&signed_block)
+            .map_err(|e| format!("Could not store sidechain block: {:?}", e))?;
+        info!(
+            "Imported sidechain block #{} of shard {} from peer",
+            block.block_number(),
+            shard.encode().to_base58()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::{ed25519, Pair, H256};
+    use substratee_worker_primitives::block::Block as SidechainBlock;
+    use substratee_worker_primitives::{BlockNumber, ShardIdentifier};
+
+    fn test_storage(name: &str) -> SidechainStorage {
+        let path = std::env::temp_dir().join(format!("sidechain_gossip_test_{}", name));
+        let _ = std::fs::remove_dir_all(&path);
+        SidechainStorage::new(path)
+    }
+
+    fn alice() -> ed25519::Pair {
+        ed25519::Pair::from_string("//Alice", None).unwrap()
+    }
+
+    fn account(pair: &ed25519::Pair) -> AccountId {
+        pair.public().into()
+    }
+
+    fn test_block(
+        number: BlockNumber,
+        parent_hash: H256,
+        shard: ShardIdentifier,
+    ) -> SignedSidechainBlock {
+        let signer_pair = alice();
+        SidechainBlock::construct_block(
+            signer_pair.public().into(),
+            number,
+            parent_hash,
+            H256::random(),
+            shard,
+            vec![],
+            vec![],
+        )
+        .sign(&signer_pair)
+    }
+
+    #[test]
+    fn import_blocks_extending_head_works() {
+        let storage = test_storage("extending_head");
+        let shard = ShardIdentifier::random();
+        let block_one = test_block(1, H256::random(), shard);
+        let block_two = test_block(2, block_one.block().hash(), shard);
+
+        let enclaves = [account(&alice())];
+        import_blocks(
+            &storage,
+            vec![block_one, block_two.clone()],
+            &enclaves,
+            |_| Ok(()),
+        )
+        .unwrap();
+
+        assert_eq!(storage.get_head_block(&shard), Some(block_two));
+    }
+
+    #[test]
+    fn blocks_of_the_producing_worker_are_imported_by_its_peer() {
+        let producer = test_storage("producer");
+        let importer = test_storage("importer");
+        let shard = ShardIdentifier::random();
+        let bob = ed25519::Pair::from_string("//Bob", None).unwrap();
+        let enclaves = [account(&alice()), account(&bob)];
+
+        // only the designated worker of the shard produces and broadcasts its blocks
+        let block_one = test_block(1, H256::random(), shard);
+        let block_two = test_block(2, block_one.block().hash(), shard);
+        let produced = vec![block_one.clone(), block_two.clone()];
+        producer.store_blocks(&produced).unwrap();
+
+        import_blocks(&importer, produced, &enclaves, |_| Ok(())).unwrap();
+
+        assert_eq!(importer.get_head_block(&shard), Some(block_two.clone()));
+        assert_eq!(importer.get_head(&shard), producer.get_head(&shard));
+
+        // a block the importer produced on its own would fork the shard and is rejected
+        let forked_block = SidechainBlock::construct_block(
+            account(&bob),
+            2,
+            block_one.block().hash(),
+            H256::random(),
+            shard,
+            vec![],
+            vec![],
+        )
+        .sign(&bob);
+        assert!(import_blocks(&producer, vec![forked_block], &enclaves, |_| Ok(())).is_err());
+        assert_eq!(producer.get_head_block(&shard), Some(block_two));
+    }
+
+    #[test]
+    fn import_block_with_wrong_parent_fails() {
+        let storage = test_storage("wrong_parent");
+        let shard = ShardIdentifier::random();
+        let block_one = test_block(1, H256::random(), shard);
+        let block_two = test_block(2, H256::random(), shard);
+
+        let enclaves = [account(&alice())];
+        assert!(import_blocks(
+            &storage,
+            vec![block_one.clone(), block_two],
+            &enclaves,
+            |_| Ok(())
+        )
+        .is_err());
+        assert_eq!(storage.get_head_block(&shard), Some(block_one));
+    }
+
+    #[test]
+    fn import_block_with_invalid_signature_fails() {
+        let storage = test_storage("invalid_signature");
+        let shard = ShardIdentifier::random();
+        let signer_pair = ed25519::Pair::from_string("//Bob", None).unwrap();
+        let author = ed25519::Pair::from_string("//Alice", None).unwrap();
+        let block = SidechainBlock::construct_block(
+            author.public().into(),
+            1,
+            H256::random(),
+            H256::random(),
+            shard,
+            vec![],
+            vec![],
+        )
+        .sign(&signer_pair);
+
+        let enclaves = [account(&author), account(&signer_pair)];
+        assert!(import_blocks(&storage, vec![block], &enclaves, |_| Ok(())).is_err());
+        assert!(storage.get_head(&shard).is_none());
+    }
+
+    #[test]
+    fn import_block_of_unregistered_author_fails() {
+        let storage = test_storage("unregistered_author");
+        let shard = ShardIdentifier::random();
+        let block = test_block(1, H256::random(), shard);
+        let bob = ed25519::Pair::from_string("//Bob", None).unwrap();
+
+        let result = import_blocks(&storage, vec![block], &[account(&bob)], |_| Ok(()));
+
+        assert!(result.is_err());
+        assert!(storage.get_head(&shard).is_none());
+    }
+
+    #[test]
+    fn block_rejected_by_import_is_not_stored() {
+        let storage = test_storage("rejected_by_import");
+        let shard = ShardIdentifier::random();
+        let block = test_block(1, H256::random(), shard);
+
+        let enclaves = [account(&alice())];
+        let result = import_blocks(&storage, vec![block], &enclaves, |_| {
+            Err("state mismatch".to_owned())
+        });
+
+        assert!(result.is_err());
+        assert!(storage.get_head(&shard).is_none());
+    }
+}
@@ -0,0 +1,269 @@
+/*
+    Copyright 2019 Supercomputing Systems AG
+
+    Licensed under the Apache License, Version 2.0 (the "License");
+    you may not use this file except in compliance with the License.
+    You may obtain a copy of the License at
+
+        http://www.apache.org/licenses/LICENSE-2.0
+
+    Unless required by applicable law or agreed to in writing, software
+    distributed under the License is distributed on an "AS IS" BASIS,
+    WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+    See the License for the specific language governing permissions and
+    limitations under the License.
+
+*/
+
+//! On-disk storage of the signed sidechain blocks produced by the enclave.
+//!
+//! Layout per shard:
+//! `<base>/<shard base58>/blocks/<block hash hex>.bin`  encoded `SignedSidechainBlock`
+//! `<base>/<shard base58>/numbers/<block number>.bin`   encoded `BlockHash`
+//! `<base>/<shard base58>/head.bin`                     encoded `(BlockNumber, BlockHash)`
+
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use base58::{FromBase58, ToBase58};
+use codec::{Decode, Encode};
+use log::*;
+
+use substratee_worker_primitives::block::{BlockId, SignedBlock as SignedSidechainBlock};
+use substratee_worker_primitives::{BlockHash, BlockNumber, ShardIdentifier};
+
+const BLOCKS_DIR: &str = "blocks";
+const NUMBERS_DIR: &str = "numbers";
+const HEAD_FILE: &str = "head.bin";
+
+pub struct SidechainStorage {
+    path: PathBuf,
+}
+
+impl SidechainStorage {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Stores the signed blocks and updates the head of the respective shard
+    pub fn store_blocks(&self, blocks: &[SignedSidechainBlock]) -> Result<()> {
+        for signed_block in blocks.iter() {
+            self.store_block(signed_block)?;
+        }
+        Ok(())
+    }
+
+    pub fn store_block(&self, signed_block: &SignedSidechainBlock) -> Result<()> {
+        let block = signed_block.block();
+        let shard = block.shard_id();
+        let number = block.block_number();
+        let hash = block.hash();
+
+        fs::create_dir_all(self.shard_path(&shard).join(BLOCKS_DIR))?;
+        fs::create_dir_all(self.shard_path(&shard).join(NUMBERS_DIR))?;
+
+        fs::write(self.block_path(&shard, &hash), signed_block.encode())?;
+        fs::write(self.number_path(&shard, number), hash.encode())?;
+
+        // the head only moves forward
+        match self.get_head(&shard) {
+            Some((head_number, _)) if head_number > number => {
+                debug!(
+                    "not updating head of shard {}: stored head #{} is ahead of block #{}",
+                    shard.encode().to_base58(),
+                    head_number,
+                    number
+                );
+            }
+            _ => fs::write(
+                self.shard_path(&shard).join(HEAD_FILE),
+                (number, hash).encode(),
+            )?,
+        };
+        debug!(
+            "stored sidechain block #{} with hash {:?} of shard {}",
+            number,
+            hash,
+            shard.encode().to_base58()
+        );
+        Ok(())
+    }
+
+    pub fn get_block_by_hash(
+        &self,
+        shard: &ShardIdentifier,
+        hash: &BlockHash,
+    ) -> Option<SignedSidechainBlock> {
+        read_decoded(&self.block_path(shard, hash))
+    }
+
+    pub fn get_block_by_number(
+        &self,
+        shard: &ShardIdentifier,
+        number: BlockNumber,
+    ) -> Option<SignedSidechainBlock> {
+        self.get_block_hash(shard, number)
+            .and_then(|hash| self.get_block_by_hash(shard, &hash))
+    }
+
+    pub fn get_block(&self, shard: &ShardIdentifier, id: &BlockId) -> Option<SignedSidechainBlock> {
+        match id {
+            BlockId::Hash(hash) => self.get_block_by_hash(shard, hash),
+            BlockId::Number(number) => self.get_block_by_number(shard, *number),
+        }
+    }
+
+    pub fn get_block_hash(
+        &self,
+        shard: &ShardIdentifier,
+        number: BlockNumber,
+    ) -> Option<BlockHash> {
+        read_decoded(&self.number_path(shard, number))
+    }
+
+    /// Returns number and hash of the latest block of the shard
+    pub fn get_head(&self, shard: &ShardIdentifier) -> Option<(BlockNumber, BlockHash)> {
+        read_decoded(&self.shard_path(shard).join(HEAD_FILE))
+    }
+
+    pub fn get_head_block(&self, shard: &ShardIdentifier) -> Option<SignedSidechainBlock> {
+        self.get_head(shard)
+            .and_then(|(_, hash)| self.get_block_by_hash(shard, &hash))
+    }
+
+    /// Lists all shards for which blocks are stored
+    pub fn shards(&self) -> Result<Vec<ShardIdentifier>> {
+        let entries = match fs::read_dir(&self.path) {
+            Ok(e) => e,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut shards = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            let shard_vec = name
+                .to_str()
+                .and_then(|n| n.from_base58().ok())
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid shard directory"))?;
+            shards.push(
+                ShardIdentifier::decode(&mut shard_vec.as_slice())
+                    .map_err(|_| Error::new(ErrorKind::InvalidData, "invalid shard directory"))?,
+            );
+        }
+        Ok(shards)
+    }
+
+    fn shard_path(&self, shard: &ShardIdentifier) -> PathBuf {
+        self.path.join(shard.encode().to_base58())
+    }
+
+    fn block_path(&self, shard: &ShardIdentifier, hash: &BlockHash) -> PathBuf {
+        self.shard_path(shard)
+            .join(BLOCKS_DIR)
+            .join(format!("{}.bin", hex::encode(hash.as_bytes())))
+    }
+
+    fn number_path(&self, shard: &ShardIdentifier, number: BlockNumber) -> PathBuf {
+        self.shard_path(shard)
+            .join(NUMBERS_DIR)
+            .join(format!("{}.bin", number))
+    }
+}
+
+fn read_decoded<T: Decode>(path: &Path) -> Option<T> {
+    let bytes = fs::read(path).ok()?;
+    match T::decode(&mut bytes.as_slice()) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            error!("could not decode {}: {:?}", path.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::{ed25519, Pair, H256};
+    use substratee_worker_primitives::block::Block as SidechainBlock;
+
+    fn test_storage(name: &str) -> SidechainStorage {
+        let path = std::env::temp_dir().join(format!("sidechain_storage_test_{}", name));
+        let _ = fs::remove_dir_all(&path);
+        SidechainStorage::new(path)
+    }
+
+    fn test_block(
+        number: BlockNumber,
+        parent_hash: H256,
+        shard: ShardIdentifier,
+    ) -> SignedSidechainBlock {
+        let signer_pair = ed25519::Pair::from_string("//Alice", None).unwrap();
+        SidechainBlock::construct_block(
+            signer_pair.public().into(),
+            number,
+            parent_hash,
+            H256::random(),
+            shard,
+            vec![H256::random()],
+            vec![1, 2, 3],
+        )
+        .sign(&signer_pair)
+    }
+
+    #[test]
+    fn store_and_get_block_works() {
+        let storage = test_storage("store_and_get");
+        let shard = ShardIdentifier::random();
+        let signed_block = test_block(1, H256::random(), shard);
+        let hash = signed_block.block().hash();
+
+        storage.store_blocks(&[signed_block.clone()]).unwrap();
+
+        assert_eq!(
+            storage.get_block_by_hash(&shard, &hash),
+            Some(signed_block.clone())
+        );
+        assert_eq!(
+            storage.get_block_by_number(&shard, 1),
+            Some(signed_block.clone())
+        );
+        assert_eq!(
+            storage.get_block(&shard, &BlockId::Hash(hash)),
+            Some(signed_block.clone())
+        );
+        assert_eq!(
+            storage.get_block(&shard, &BlockId::Number(1)),
+            Some(signed_block)
+        );
+        assert_eq!(storage.get_head(&shard), Some((1, hash)));
+        assert_eq!(storage.shards().unwrap(), vec![shard]);
+    }
+
+    #[test]
+    fn head_does_not_move_backwards() {
+        let storage = test_storage("head_backwards");
+        let shard = ShardIdentifier::random();
+        let block_one = test_block(1, H256::random(), shard);
+        let block_two = test_block(2, block_one.block().hash(), shard);
+
+        storage.store_block(&block_two).unwrap();
+        storage.store_block(&block_one).unwrap();
+
+        assert_eq!(storage.get_head_block(&shard), Some(block_two));
+        assert_eq!(storage.get_block_by_number(&shard, 1), Some(block_one));
+    }
+
+    #[test]
+    fn unknown_block_returns_none() {
+        let storage = test_storage("unknown");
+        let shard = ShardIdentifier::random();
+
+        assert!(storage.get_head(&shard).is_none());
+        assert!(storage.get_block_by_number(&shard, 0).is_none());
+        assert!(storage.shards().unwrap().is_empty());
+    }
+}
@@ -20,6 +20,7 @@ use crate::init_shard;
 use crate::tests::commons::test_trusted_getter_signed;
 use codec::Encode;
 use sp_keyring::AccountKeyring;
+use substratee_stf::GenesisConfig;
 
 use sgx_types::*;
 use sp_core::hash::H256;
@@ -28,7 +29,7 @@ pub fn get_state_works(eid: sgx_enclave_id_t) {
     let alice = AccountKeyring::Alice;
     let trusted_getter_signed = test_trusted_getter_signed(alice).encode();
     let shard = H256::default();
-    init_shard(&shard);
+    init_shard(eid, &shard, &GenesisConfig::default());
     let res = enclave_query_state(eid, trusted_getter_signed, shard.encode()).unwrap();
     println!("get_state returned {:?}", res);
 }
@@ -31,6 +31,8 @@ use std::thread::sleep;
 use std::time::Duration;
 use substrate_api_client::{compose_extrinsic, extrinsic::xt_primitives::UncheckedExtrinsicV4};
 use substratee_node_primitives::{CallWorkerFn, Request, ShieldFundsFn};
+use substratee_stf::NATIVE_ASSET_ID;
+use substratee_worker_primitives::RelayId;
 
 pub fn perform_ra_works(eid: sgx_enclave_id_t, port: &str) {
     // start the substrate-api-client to communicate with the node
@@ -54,6 +56,7 @@ pub fn perform_ra_works(eid: sgx_enclave_id_t, port: &str) {
 pub fn call_worker_encrypted_set_balance_works(
     eid: sgx_enclave_id_t,
     port: &str,
+    relay_id: RelayId,
     last_synced_head: Header,
 ) -> Header {
     let root = AccountKeyring::Alice; // Alice is configure as root in our STF
@@ -72,11 +75,12 @@ pub fn call_worker_encrypted_set_balance_works(
     println!("Sleeping until block with shield funds is finalized...");
     sleep(Duration::new(10, 0));
     println!("Syncing Chain Relay to look for shield_funds extrinsic");
-    crate::produce_blocks(eid, &api, last_synced_head)
+    crate::produce_blocks(eid, &api, relay_id, last_synced_head)
 }
 pub fn forward_encrypted_unshield_works(
     eid: sgx_enclave_id_t,
     port: &str,
+    relay_id: RelayId,
     last_synced_head: Header,
 ) -> Header {
     let (api, nonce, shard) = setup(eid, Some(AccountKeyring::Alice), port);
@@ -94,15 +98,24 @@ pub fn forward_encrypted_unshield_works(
     println!("Sleeping until block with shield funds is finalized...");
     sleep(Duration::new(10, 0));
     println!("Syncing Chain Relay to look for CallWorker with TrustedCall::unshield extrinsic");
-    crate::produce_blocks(eid, &api, last_synced_head)
+    crate::produce_blocks(eid, &api, relay_id, last_synced_head)
 }
 
-pub fn init_chain_relay(eid: sgx_enclave_id_t, port: &str) -> Header {
+pub fn init_chain_relay(eid: sgx_enclave_id_t, port: &str) -> (RelayId, Header) {
     let (api, _, _) = setup(eid, None, port);
-    crate::init_chain_relay(eid, &api)
+    let (relay_id, latest_head) = crate::init_chain_relay(eid, &api, false, true);
+    (
+        relay_id,
+        crate::produce_blocks(eid, &api, relay_id, latest_head),
+    )
 }
 
-pub fn shield_funds_workds(eid: sgx_enclave_id_t, port: &str, last_synced_head: Header) -> Header {
+pub fn shield_funds_workds(
+    eid: sgx_enclave_id_t,
+    port: &str,
+    relay_id: RelayId,
+    last_synced_head: Header,
+) -> Header {
     let (api, _nonce, shard) = setup(eid, Some(AccountKeyring::Alice), port);
 
     let xt: UncheckedExtrinsicV4<ShieldFundsFn> = compose_extrinsic!(
@@ -110,6 +123,7 @@ pub fn shield_funds_workds(eid: sgx_enclave_id_t, port: &str, last_synced_head:
         "SubstrateeRegistry",
         "shield_funds",
         encrypted_alice(eid),
+        NATIVE_ASSET_ID,
         444u128,
         shard
     );
@@ -121,5 +135,5 @@ pub fn shield_funds_workds(eid: sgx_enclave_id_t, port: &str, last_synced_head:
     println!("Sleeping until block with shield funds is finalized...");
     sleep(Duration::new(10, 0));
     println!("Syncing Chain Relay to look for shield_funds extrinsic");
-    crate::produce_blocks(eid, &api, last_synced_head)
+    crate::produce_blocks(eid, &api, relay_id, last_synced_head)
 }
@@ -51,13 +51,13 @@ pub fn run_enclave_tests(matches: &ArgMatches, port: &str) {
         println!("  testing perform_ra()");
         perform_ra_works(eid, port);
         println!("  init chain_relay");
-        let mut head = init_chain_relay(eid, port);
+        let (relay_id, mut head) = init_chain_relay(eid, port);
         println!("  testing process_forwarded_payload()");
-        head = call_worker_encrypted_set_balance_works(eid, port, head);
+        head = call_worker_encrypted_set_balance_works(eid, port, relay_id, head);
         println!("  testing execute_stf_unshield_balance()");
-        head = forward_encrypted_unshield_works(eid, port, head);
+        head = forward_encrypted_unshield_works(eid, port, relay_id, head);
         println!("  testing shield_funds");
-        let _head = shield_funds_workds(eid, port, head);
+        let _head = shield_funds_workds(eid, port, relay_id, head);
     }
     println!("[+] All tests ended!");
 }
@@ -3,11 +3,18 @@ use std::sync::mpsc::channel;
 use std::sync::mpsc::Sender as MpscSender;
 use std::thread;
 
-use codec::Decode;
+use codec::{Decode, Encode};
+use sp_core::{crypto::AccountId32, H256};
 
 use ws::{connect, CloseCode, Handler, Handshake, Message, Result as ClientResult, Sender};
 
-use substratee_worker_primitives::{DirectRequestStatus, RpcRequest, RpcResponse, RpcReturnValue};
+use substratee_worker_primitives::block::{
+    BlockId, Header as SidechainHeader, SignedBlock as SignedSidechainBlock,
+};
+use substratee_worker_primitives::{
+    request_hash, BlockHash, BlockNumber, DirectRequestStatus, RpcRequest, RpcResponse,
+    RpcReturnValue, ShardIdentifier,
+};
 
 use sgx_crypto_helper::rsa3072::Rsa3072PubKey;
 
@@ -40,11 +47,81 @@ impl Handler for DirectWsClient {
 #[derive(Clone)]
 pub struct DirectApi {
     url: String,
+    /// signing key of the enclave as registered on chain
+    enclave_signer: Option<AccountId32>,
 }
 
 impl DirectApi {
     pub fn new(url: String) -> Self {
-        Self { url }
+        Self {
+            url,
+            enclave_signer: None,
+        }
+    }
+
+    /// Only accepts responses of the enclave which are signed by `signer`
+    pub fn with_enclave_signer(mut self, signer: AccountId32) -> Self {
+        self.enclave_signer = Some(signer);
+        self
+    }
+
+    /// Decodes the return value of an enclave response to the request with the given hash
+    ///
+    /// If an enclave signer is set, the signature of the return value is verified against it,
+    /// as well as that the value answers the request made for `shard`.
+    pub fn decode_return_value(
+        &self,
+        response_str: &str,
+        request_hash: &H256,
+        shard: &ShardIdentifier,
+    ) -> Result<RpcReturnValue, String> {
+        let response: RpcResponse = serde_json::from_str(response_str)
+            .map_err(|e| format!("Could not decode rpc response: {:?}", e))?;
+        let return_value = RpcReturnValue::decode(&mut response.result.as_slice())
+            .map_err(|e| format!("Could not decode return value: {:?}", e))?;
+        if let Some(signer) = &self.enclave_signer {
+            if !return_value.verify_signature(signer) {
+                return Err("Return value is not signed by the registered enclave".to_owned());
+            }
+            if !return_value.answers(request_hash, shard) {
+                return Err("Return value does not answer the request".to_owned());
+            }
+        }
+        Ok(return_value)
+    }
+
+    /// Decodes the return value of a response the worker composed outside of the enclave
+    ///
+    /// Such a value is not signed by the enclave, so the caller has to verify its content itself.
+    pub fn decode_untrusted_return_value(
+        &self,
+        response_str: &str,
+    ) -> Result<RpcReturnValue, String> {
+        let response: RpcResponse = serde_json::from_str(response_str)
+            .map_err(|e| format!("Could not decode rpc response: {:?}", e))?;
+        let return_value = RpcReturnValue::decode(&mut response.result.as_slice())
+            .map_err(|e| format!("Could not decode return value: {:?}", e))?;
+        if !return_value.is_untrusted() {
+            return Err("Expected a return value composed outside of the enclave".to_owned());
+        }
+        Ok(return_value)
+    }
+
+    /// Decodes a sidechain header pushed to a subscriber of the heads of `shard`
+    ///
+    /// The worker pushes the headers outside of the enclave, so they are accepted as untrusted.
+    pub fn decode_sidechain_head(
+        &self,
+        response_str: &str,
+        shard: &ShardIdentifier,
+    ) -> Result<SidechainHeader, String> {
+        let return_value = self.decode_untrusted_return_value(response_str)?;
+        let header = SidechainHeader::decode(&mut return_value.value.as_slice())
+            .map_err(|e| format!("Could not decode sidechain header: {:?}", e))?;
+        if header.shard_id != *shard {
+            return Err("Sidechain header belongs to another shard".to_owned());
+        }
+        Ok(header)
     }
 
     /// server connection with only one response
@@ -100,6 +177,7 @@ impl DirectApi {
     pub fn get_rsa_pubkey(&self) -> Result<Rsa3072PubKey, String> {
         // compose jsonrpc call
         let method = "author_getShieldingKey".to_owned();
+        let request_hash = request_hash(&method, &[]);
         let jsonrpc_call: String = RpcRequest::compose_jsonrpc_call(method, vec![]);
 
         let response_str = match Self::get(&self, jsonrpc_call) {
@@ -109,19 +187,14 @@ impl DirectApi {
             }
         };
 
-        // decode result
-        let response: RpcResponse = match serde_json::from_str(&response_str) {
-            Ok(resp) => resp,
-            Err(err_msg) => {
-                return Err(format! {"Could not retrieve shielding pubkey: {:?}", err_msg})
-            }
-        };
-        let return_value = match RpcReturnValue::decode(&mut response.result.as_slice()) {
-            Ok(val) => val,
-            Err(err_msg) => {
-                return Err(format! {"Could not retrieve shielding pubkey: {:?}", err_msg})
-            }
-        };
+        // decode and verify result
+        let return_value =
+            match self.decode_return_value(&response_str, &request_hash, &Default::default()) {
+                Ok(val) => val,
+                Err(err_msg) => {
+                    return Err(format! {"Could not retrieve shielding pubkey: {}", err_msg})
+                }
+            };
         let shielding_pubkey_string: String = match return_value.status {
             DirectRequestStatus::Ok => match String::decode(&mut return_value.value.as_slice()) {
                 Ok(key) => key,
@@ -142,4 +215,103 @@ impl DirectApi {
         info!("[+] Got RSA public key of enclave");
         Ok(shielding_pubkey)
     }
+
+    /// Sends sidechain blocks to a peer worker, which imports them into its block store
+    pub fn send_sidechain_blocks(&self, blocks: Vec<SignedSidechainBlock>) -> Result<(), String> {
+        // compose jsonrpc call
+        let method = "sidechain_importBlock".to_owned();
+        let jsonrpc_call: String = RpcRequest::compose_jsonrpc_call(method, blocks.encode());
+
+        let response_str = match Self::get(&self, jsonrpc_call) {
+            Ok(resp) => resp,
+            Err(err_msg) => return Err(format! {"Could not send sidechain blocks: {:?}", err_msg}),
+        };
+
+        // decode result
+        let response: RpcResponse = match serde_json::from_str(&response_str) {
+            Ok(resp) => resp,
+            Err(err_msg) => return Err(format! {"Could not send sidechain blocks: {:?}", err_msg}),
+        };
+        let return_value = match RpcReturnValue::decode(&mut response.result.as_slice()) {
+            Ok(val) => val,
+            Err(err_msg) => return Err(format! {"Could not send sidechain blocks: {:?}", err_msg}),
+        };
+        match return_value.status {
+            DirectRequestStatus::Ok => Ok(()),
+            _ => match String::decode(&mut return_value.value.as_slice()) {
+                Ok(err_msg) => Err(format! {"Peer rejected sidechain blocks: {}", err_msg}),
+                Err(err) => Err(format! {"Could not send sidechain blocks: {:?}", err}),
+            },
+        }
+    }
+
+    /// Returns the sidechain block of the shard with the given hash or number
+    ///
+    /// The worker answers from its untrusted block storage, so the signature of the block
+    /// author is verified here. Whether the author is a registered enclave is up to the caller.
+    pub fn get_sidechain_block(
+        &self,
+        shard: ShardIdentifier,
+        id: BlockId,
+    ) -> Result<Option<SignedSidechainBlock>, String> {
+        let signed_block: Option<SignedSidechainBlock> =
+            self.query_sidechain("chain_getBlock", (shard, id).encode())?;
+        if let Some(signed_block) = &signed_block {
+            if !signed_block.verify_signature() {
+                return Err("Sidechain block has an invalid author signature".to_owned());
+            }
+            if signed_block.block().shard_id() != shard {
+                return Err("Sidechain block belongs to another shard".to_owned());
+            }
+        }
+        Ok(signed_block)
+    }
+
+    /// Returns the header of the sidechain block of the shard with the given hash or number
+    ///
+    /// Untrusted: the worker answers from its block storage, use `get_sidechain_block`
+    /// to verify the block.
+    pub fn get_sidechain_header(
+        &self,
+        shard: ShardIdentifier,
+        id: BlockId,
+    ) -> Result<Option<SidechainHeader>, String> {
+        self.query_sidechain("chain_getHeader", (shard, id).encode())
+    }
+
+    /// Returns the hash of the sidechain block with the given number, or of the head if `None`
+    ///
+    /// Untrusted: the worker answers from its block storage.
+    pub fn get_sidechain_block_hash(
+        &self,
+        shard: ShardIdentifier,
+        number: Option<BlockNumber>,
+    ) -> Result<Option<BlockHash>, String> {
+        self.query_sidechain("chain_getBlockHash", (shard, number).encode())
+    }
+
+    // sidechain queries are answered by the worker outside of the enclave
+    fn query_sidechain<T: Decode>(&self, method: &str, params: Vec<u8>) -> Result<T, String> {
+        // compose jsonrpc call
+        let jsonrpc_call: String = RpcRequest::compose_jsonrpc_call(method.to_owned(), params);
+
+        let response_str = match Self::get(&self, jsonrpc_call) {
+            Ok(resp) => resp,
+            Err(err_msg) => return Err(format! {"Could not query {}: {:?}", method, err_msg}),
+        };
+
+        // decode result
+        let return_value = match self.decode_untrusted_return_value(&response_str) {
+            Ok(val) => val,
+            Err(err_msg) => return Err(format! {"Could not query {}: {}", method, err_msg}),
+        };
+        match return_value.status {
+            DirectRequestStatus::Ok => T::decode(&mut return_value.value.as_slice())
+                .map_err(|err| format! {"Could not query {}: {:?}", method, err}),
+            _ => match String::decode(&mut return_value.value.as_slice()) {
+                Ok(err_msg) => Err(format! {"Could not query {}: {}", method, err_msg}),
+                Err(err) => Err(format! {"Could not query {}: {:?}", method, err}),
+            },
+        }
+    }
 }